@@ -36,11 +36,13 @@
 pub mod symbolic;
 pub mod tensor;
 pub mod einstein;
+pub mod perturbation;
 
 // Re-export commonly used types and functions
 pub use symbolic::*;
 pub use tensor::*;
 pub use einstein::*;
+pub use perturbation::*;
 
 // Re-export error type
 use serde::{Deserialize, Serialize};
@@ -54,6 +56,8 @@ pub enum TensorError {
     InvalidMetric(String),
     #[error("Computation error: {0}")]
     ComputationError(String),
+    #[error("Parse error: {0}")]
+    ParseError(String),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,6 +1,6 @@
-use clap::{Parser, Subcommand};
-use serde_json;
+use clap::{Args, Parser, Subcommand};
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
 use tensor_calc::*;
 
 #[derive(Parser)]
@@ -9,61 +9,99 @@ use tensor_calc::*;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Output format; `latex` is supported by the curvature subcommands
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// Numeric parameter values to substitute into the result, e.g. '{"M": 1}'
+    #[arg(long, global = true)]
+    params: Option<String>,
+    /// Print curvature components one per line in index notation instead of JSON
+    #[arg(long, global = true, conflicts_with = "format")]
+    pretty: bool,
+    /// Add the parsed expression tree under `expression_tree` next to each component expression
+    #[arg(long, global = true, conflicts_with_all = ["format", "pretty"])]
+    tree: bool,
+    /// Like `--pretty`, showing Greek-letter names such as `theta` or `Omega_m` as `θ` or `Ω_m`
+    #[arg(long, global = true, conflicts_with_all = ["format", "tree"])]
+    unicode: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Json,
+    Latex,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Gauge {
+    /// No gauge condition; the `G_0ν` constraint equations instead
+    None,
+    /// Harmonic gauge, `g^μν Γ^λ_μν = 0`
+    Harmonic,
+}
+
+impl Gauge {
+    fn name(self) -> &'static str {
+        match self {
+            Gauge::None => "none",
+            Gauge::Harmonic => "harmonic",
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Compute Christoffel symbols from a metric tensor
     Christoffel {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
     },
     /// Compute Riemann curvature tensor
     Riemann {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
     },
     /// Compute Ricci tensor
     Ricci {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
     },
     /// Compute Ricci scalar
     RicciScalar {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
+    },
+    /// Compute the Kretschmann scalar R_abcd R^abcd
+    Kretschmann {
+        #[command(flatten)]
+        input: MetricArgs,
     },
     /// Compute Einstein tensor
     Einstein {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
+    },
+    /// Compute the geodesic equations of a metric
+    Geodesics {
+        #[command(flatten)]
+        input: MetricArgs,
+    },
+    /// Compute the metric determinant
+    Determinant {
+        #[command(flatten)]
+        input: MetricArgs,
+    },
+    /// Compute the inverse metric
+    Inverse {
+        #[command(flatten)]
+        input: MetricArgs,
     },
     /// Solve Einstein field equations for vacuum spacetimes
     SolveVacuum {
         /// Coordinate variables in JSON array format
         #[arg(long)]
         coords: String,
-        /// Symmetry ansatz: "spherical", "cosmological", "axisymmetric"
+        /// Symmetry ansatz: "spherical", "cosmological", "axisymmetric", "radiating", "plane_wave", "godel"
         #[arg(long)]
         symmetry: String,
         /// Boundary conditions in JSON format (optional)
@@ -72,12 +110,8 @@ enum Commands {
     },
     /// Verify that a metric solves Einstein field equations
     VerifySolution {
-        /// Metric tensor in JSON format
-        #[arg(long)]
-        metric: String,
-        /// Coordinate variables in JSON array format
-        #[arg(long)]
-        coords: String,
+        #[command(flatten)]
+        input: MetricArgs,
         /// Stress-energy tensor in JSON format (optional for vacuum)
         #[arg(long)]
         stress_energy: Option<String>,
@@ -96,40 +130,190 @@ enum Commands {
         /// Cosmological constant (optional)
         #[arg(long)]
         lambda: Option<String>,
+        /// Gauge condition to add
+        #[arg(long, value_enum, default_value_t = Gauge::None)]
+        gauge: Gauge,
+    },
+    /// Start an interactive session reading commands line-by-line
+    Repl,
+    /// Run a JSON array of {metric, coords, operation} jobs and print an array of results
+    Batch {
+        /// Path to the JSON jobs file
+        #[arg(long)]
+        file: String,
     },
 }
 
+/// Metric and coordinates given inline as JSON or read from a file.
+#[derive(Args)]
+struct MetricArgs {
+    /// Metric tensor in JSON format
+    #[arg(long, required_unless_present_any = ["metric_file", "diagonal"])]
+    metric: Option<String>,
+    /// Diagonal of a diagonal metric as a JSON array, instead of the full --metric
+    #[arg(long, conflicts_with_all = ["metric", "metric_file"])]
+    diagonal: Option<String>,
+    /// Coordinate variables in JSON array format
+    #[arg(long, required_unless_present = "metric_file")]
+    coords: Option<String>,
+    /// JSON (or, with the `yaml` feature, YAML) file with `metric` and `coords` keys
+    #[arg(long, conflicts_with_all = ["metric", "coords"])]
+    metric_file: Option<String>,
+}
+
+/// A metric with its coordinates, as given on the command line or in a file.
+#[derive(serde::Deserialize)]
+struct MetricInput {
+    metric: Vec<Vec<String>>,
+    coords: Vec<String>,
+}
+
+impl MetricArgs {
+    fn load(&self) -> Result<MetricInput, TensorError> {
+        match (&self.metric_file, &self.metric, &self.diagonal, &self.coords) {
+            (Some(path), _, _, _) => load_metric_file(path),
+            (None, Some(metric), _, Some(coords)) => Ok(MetricInput {
+                metric: serde_json::from_str(metric)?,
+                coords: serde_json::from_str(coords)?,
+            }),
+            (None, None, Some(diagonal), Some(coords)) => {
+                let coords: Vec<String> = serde_json::from_str(coords)?;
+                let metric = parse_diagonal_metric(serde_json::from_str(diagonal)?, &coords)?;
+                let metric = metric.iter()
+                    .map(|row| row.iter().map(|entry| entry.to_string()).collect())
+                    .collect();
+                Ok(MetricInput { metric, coords })
+            }
+            _ => Err(TensorError::ComputationError(
+                "Either --metric or --diagonal with --coords, or --metric-file is required".to_string()
+            )),
+        }
+    }
+}
+
+fn load_metric_file(path: &str) -> Result<MetricInput, TensorError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| TensorError::ComputationError(format!("Failed to read '{}': {}", path, e)))?;
+
+    if path.ends_with(".yaml") || path.ends_with(".yml") {
+        parse_yaml_metric(&contents)
+    } else {
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml_metric(contents: &str) -> Result<MetricInput, TensorError> {
+    serde_yaml::from_str(contents)
+        .map_err(|e| TensorError::InvalidMetric(format!("Invalid YAML metric file: {}", e)))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn parse_yaml_metric(_contents: &str) -> Result<MetricInput, TensorError> {
+    Err(TensorError::ComputationError(
+        "YAML metric files require building with the `yaml` feature".to_string()
+    ))
+}
 
 fn main() {
     let cli = Cli::parse();
+    let format = cli.format;
+    let pretty = cli.pretty;
+    let params = cli.params.clone();
+    let tree = cli.tree;
+    let unicode = cli.unicode;
 
     let result = match cli.command {
-        Commands::Christoffel { metric, coords } => {
-            compute_christoffel_symbols(&metric, &coords)
+        Commands::Christoffel { input } => {
+            input.load().and_then(compute_christoffel_symbols)
+        }
+        // Print large Riemann outputs as they are computed rather than all at the end
+        Commands::Riemann { input } if (pretty || unicode) && params.is_none() => {
+            input.load()
+                .and_then(|input| print_riemann_components(input, unicode, &mut io::stdout()))
+                .map(|()| std::process::exit(0))
+        }
+        Commands::Riemann { input } => {
+            input.load().and_then(compute_riemann_tensor)
+        }
+        Commands::Ricci { input } => {
+            input.load().and_then(compute_ricci_tensor)
         }
-        Commands::Riemann { metric, coords } => {
-            compute_riemann_tensor(&metric, &coords)
+        Commands::RicciScalar { input } => {
+            input.load().and_then(compute_ricci_scalar)
         }
-        Commands::Ricci { metric, coords } => {
-            compute_ricci_tensor(&metric, &coords)
+        Commands::Kretschmann { input } => {
+            input.load().and_then(compute_kretschmann)
         }
-        Commands::RicciScalar { metric, coords } => {
-            compute_ricci_scalar(&metric, &coords)
+        Commands::Einstein { input } => {
+            input.load().and_then(compute_einstein_tensor)
         }
-        Commands::Einstein { metric, coords } => {
-            compute_einstein_tensor(&metric, &coords)
+        Commands::Geodesics { input } => {
+            input.load().and_then(compute_geodesic_equations)
+        }
+        Commands::Determinant { input } => {
+            input.load().and_then(compute_metric_determinant)
+        }
+        Commands::Inverse { input } => {
+            input.load().and_then(compute_metric_inverse)
         }
         Commands::SolveVacuum { coords, symmetry, boundary_conditions } => {
             solve_vacuum_equations(&coords, &symmetry, boundary_conditions.as_deref())
         }
-        Commands::VerifySolution { metric, coords, stress_energy, lambda } => {
-            verify_solution(&metric, &coords, stress_energy.as_deref(), lambda.as_deref())
+        Commands::VerifySolution { input, stress_energy, lambda } => {
+            input.load().and_then(|input| verify_solution(input, stress_energy.as_deref(), lambda.as_deref()))
+        }
+        Commands::ConstructEquations { stress_energy, coords, lambda, gauge } => {
+            construct_field_equations(&stress_energy, &coords, lambda.as_deref(), gauge)
+        }
+        Commands::Repl => {
+            let stdin = io::stdin();
+            let mut stdout = io::stdout();
+            match run_repl(stdin.lock(), &mut stdout) {
+                Ok(()) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
         }
-        Commands::ConstructEquations { stress_energy, coords, lambda } => {
-            construct_field_equations(&stress_energy, &coords, lambda.as_deref())
+        Commands::Batch { file } => {
+            match std::fs::read_to_string(&file) {
+                Ok(jobs_json) => match run_batch(&jobs_json) {
+                    Ok(results) => {
+                        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+                        std::process::exit(0);
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(TensorError::ComputationError(format!("Failed to read '{}': {}", file, e))),
+            }
         }
     };
 
+    let result = match params {
+        Some(params_json) => result.and_then(|result| substitute_params(result, &params_json)),
+        None => result,
+    };
+    let result = match result {
+        Ok(result) if tree => add_expression_trees(result),
+        result => result,
+    };
+
+    let rendered = match &result {
+        Ok(tensor_result) if pretty || unicode => Some(render_pretty(tensor_result, unicode)),
+        Ok(tensor_result) if format == OutputFormat::Latex => Some(render_latex(tensor_result)),
+        _ => None,
+    };
+    let result = match rendered {
+        Some(Ok(text)) => {
+            println!("{}", text);
+            std::process::exit(0);
+        }
+        Some(Err(e)) => Err(e),
+        None => result,
+    };
+
     match result {
         Ok(tensor_result) => {
             println!("{}", serde_json::to_string_pretty(&tensor_result).unwrap());
@@ -149,9 +333,157 @@ fn main() {
     }
 }
 
-fn compute_christoffel_symbols(metric_json: &str, coords_json: &str) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+/// Substitutes numeric parameter values into every component expression of a result.
+/// Components left without free symbols are evaluated to a number; the rest are
+/// simplified and stay symbolic. A component that cannot be evaluated at the given
+/// point, e.g. one dividing by zero, stays symbolic and is marked `"singular": true`.
+fn substitute_params(mut result: TensorResult, params_json: &str) -> Result<TensorResult, TensorError> {
+    let params: HashMap<String, f64> = serde_json::from_str(params_json)?;
+    substitute_params_in(&mut result.data, &params)?;
+    Ok(result)
+}
+
+fn substitute_params_in(value: &mut serde_json::Value, params: &HashMap<String, f64>) -> Result<(), TensorError> {
+    match value {
+        serde_json::Value::Object(fields) => {
+            if let Some(serde_json::Value::String(expression)) = fields.get_mut("expression") {
+                let mut expr = SymbolicExpr::parse(expression)?;
+                for (name, param) in params {
+                    expr = expr.substitute(name, &SymbolicExpr::Constant(*param));
+                }
+                let value = expr.free_symbols().is_empty().then(|| expr.eval(&HashMap::new()));
+                let singular = matches!(value, Some(Err(_)));
+                *expression = match value {
+                    Some(Ok(value)) => SymbolicExpr::Constant(value),
+                    _ => expr.simplify(),
+                }.to_string();
+                if singular {
+                    fields.insert("singular".to_string(), serde_json::Value::Bool(true));
+                }
+            }
+            for field in fields.values_mut() {
+                substitute_params_in(field, params)?;
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                substitute_params_in(item, params)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Adds an `expression_tree` field holding the serialized `SymbolicExpr` next to
+/// every component expression of a result.
+fn add_expression_trees(mut result: TensorResult) -> Result<TensorResult, TensorError> {
+    add_expression_trees_in(&mut result.data)?;
+    Ok(result)
+}
+
+fn add_expression_trees_in(value: &mut serde_json::Value) -> Result<(), TensorError> {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for field in fields.values_mut() {
+                add_expression_trees_in(field)?;
+            }
+            if let Some(serde_json::Value::String(expression)) = fields.get("expression") {
+                let tree = serde_json::to_value(SymbolicExpr::parse(expression)?)?;
+                fields.insert("expression_tree".to_string(), tree);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                add_expression_trees_in(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The tensor name, upper/lower index mask and components of a curvature result.
+fn indexed_components(result: &TensorResult) -> Result<(&'static str, &'static [bool], Vec<TensorComponent>), TensorError> {
+    match result.result_type.as_str() {
+        "christoffel_symbols" => {
+            let christoffel: ChristoffelResult = serde_json::from_value(result.data.clone())?;
+            Ok(("Gamma", &[true, false, false], christoffel.symbols))
+        }
+        "riemann_tensor" => {
+            let riemann: RiemannResult = serde_json::from_value(result.data.clone())?;
+            Ok(("R", &[true, false, false, false], riemann.components))
+        }
+        "ricci_tensor" => {
+            let ricci: RiemannResult = serde_json::from_value(result.data.clone())?;
+            Ok(("R", &[false, false], ricci.components))
+        }
+        "einstein_tensor" => {
+            let einstein: RiemannResult = serde_json::from_value(result.data.clone())?;
+            Ok(("G", &[false, false], einstein.components))
+        }
+        "ricci_scalar" => {
+            let scalar: TensorComponent = serde_json::from_value(result.data.clone())?;
+            Ok(("R", &[], vec![scalar]))
+        }
+        "kretschmann_scalar" => {
+            let scalar: TensorComponent = serde_json::from_value(result.data.clone())?;
+            Ok(("K", &[], vec![scalar]))
+        }
+        other => Err(TensorError::ComputationError(
+            format!("Indexed output is not supported for {}", other)
+        )),
+    }
+}
+
+/// Renders a curvature result as one line per component, e.g. `Γ^{1}_{22} = -r`, with
+/// Greek-letter names in the expressions shown as symbols if `unicode` is set.
+fn render_pretty(result: &TensorResult, unicode: bool) -> Result<String, TensorError> {
+    let (name, upper, components) = indexed_components(result)?;
+    let name = if name == "Gamma" { "Γ" } else { name };
+    components.iter()
+        .map(|component| Ok(pretty_component(component, unicode)?.format_indexed(name, upper)))
+        .collect::<Result<Vec<_>, TensorError>>()
+        .map(|lines| lines.join("\n"))
+}
+
+fn pretty_component(component: &TensorComponent, unicode: bool) -> Result<TensorComponent, TensorError> {
+    let mut component = component.clone();
+    if unicode {
+        component.expression = SymbolicExpr::parse(&component.expression)?.to_unicode();
+    }
+    Ok(component)
+}
+
+/// Renders a curvature result as one LaTeX equation per component, e.g.
+/// `\Gamma^{r}_{\theta \theta} = -r`.
+fn render_latex(result: &TensorResult) -> Result<String, TensorError> {
+    let (name, upper, components) = indexed_components(result)?;
+    let name = SymbolicExpr::Variable(name.to_string()).to_latex();
+
+    let mut lines = Vec::new();
+    for component in components {
+        let mut label = name.clone();
+        // Consecutive indices in the same position share one ^{...} or _{...} group
+        let mut position = 0;
+        while position < component.indices.len() {
+            let is_upper = upper[position];
+            let mut group = Vec::new();
+            while position < component.indices.len() && upper[position] == is_upper {
+                let coordinate = &result.coordinates[component.indices[position]];
+                group.push(SymbolicExpr::Variable(coordinate.clone()).to_latex());
+                position += 1;
+            }
+            label.push_str(&format!("{}{{{}}}", if is_upper { "^" } else { "_" }, group.join(" ")));
+        }
+        let expression = SymbolicExpr::parse(&component.expression)?;
+        lines.push(format!("{} = {}", label, expression.to_latex()));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn compute_christoffel_symbols(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     let christoffel = calculate_christoffel_symbols(&parsed_metric, &coords)?;
@@ -165,9 +497,8 @@ fn compute_christoffel_symbols(metric_json: &str, coords_json: &str) -> Result<T
     })
 }
 
-fn compute_riemann_tensor(metric_json: &str, coords_json: &str) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+fn compute_riemann_tensor(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     let riemann = calculate_riemann_tensor(&parsed_metric, &coords)?;
@@ -181,9 +512,22 @@ fn compute_riemann_tensor(metric_json: &str, coords_json: &str) -> Result<Tensor
     })
 }
 
-fn compute_ricci_tensor(metric_json: &str, coords_json: &str) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+/// Writes Riemann components in index notation one line at a time as they are computed.
+fn print_riemann_components<W: Write>(input: MetricInput, unicode: bool, output: &mut W) -> Result<(), TensorError> {
+    let MetricInput { metric, coords } = input;
+    let parsed_metric = parse_metric_tensor(metric, &coords)?;
+    
+    for component in riemann_components_iter(&parsed_metric, &coords, &CurvatureOptions::default()) {
+        let line = pretty_component(&component?, unicode)?.format_indexed("R", &[true, false, false, false]);
+        writeln!(output, "{}", line)
+            .and_then(|()| output.flush())
+            .map_err(|e| TensorError::ComputationError(format!("Failed to write output: {}", e)))?;
+    }
+    Ok(())
+}
+
+fn compute_ricci_tensor(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     let ricci = calculate_ricci_tensor(&parsed_metric, &coords)?;
@@ -197,9 +541,8 @@ fn compute_ricci_tensor(metric_json: &str, coords_json: &str) -> Result<TensorRe
     })
 }
 
-fn compute_ricci_scalar(metric_json: &str, coords_json: &str) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+fn compute_ricci_scalar(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     let scalar = calculate_ricci_scalar(&parsed_metric, &coords)?;
@@ -213,9 +556,23 @@ fn compute_ricci_scalar(metric_json: &str, coords_json: &str) -> Result<TensorRe
     })
 }
 
-fn compute_einstein_tensor(metric_json: &str, coords_json: &str) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+fn compute_kretschmann(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
+    
+    let parsed_metric = parse_metric_tensor(metric, &coords)?;
+    let scalar = calculate_kretschmann_scalar(&parsed_metric, &coords)?;
+    
+    Ok(TensorResult {
+        result_type: "kretschmann_scalar".to_string(),
+        data: serde_json::to_value(scalar)?,
+        coordinates: coords,
+        success: true,
+        error: None,
+    })
+}
+
+fn compute_einstein_tensor(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     let einstein = calculate_einstein_tensor(&parsed_metric, &coords)?;
@@ -229,6 +586,57 @@ fn compute_einstein_tensor(metric_json: &str, coords_json: &str) -> Result<Tenso
     })
 }
 
+fn compute_geodesic_equations(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
+    
+    let parsed_metric = parse_metric_tensor(metric, &coords)?;
+    let equations = geodesic_equations(&parsed_metric, &coords)?;
+    
+    Ok(TensorResult {
+        result_type: "geodesic_equations".to_string(),
+        data: serde_json::to_value(equations)?,
+        coordinates: coords,
+        success: true,
+        error: None,
+    })
+}
+
+fn compute_metric_determinant(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
+    
+    let parsed_metric = parse_metric_tensor(metric, &coords)?;
+    let determinant = TensorComponent {
+        indices: vec![],
+        expression: calculate_metric_determinant(&parsed_metric)?.to_string(),
+    };
+    
+    Ok(TensorResult {
+        result_type: "metric_determinant".to_string(),
+        data: serde_json::to_value(determinant)?,
+        coordinates: coords,
+        success: true,
+        error: None,
+    })
+}
+
+fn compute_metric_inverse(input: MetricInput) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
+    
+    let parsed_metric = parse_metric_tensor(metric, &coords)?;
+    let inverse: Vec<Vec<String>> = calculate_metric_inverse(&parsed_metric)?
+        .iter()
+        .map(|row| row.iter().map(|component| component.simplify().to_string()).collect())
+        .collect();
+    
+    Ok(TensorResult {
+        result_type: "inverse_metric".to_string(),
+        data: serde_json::to_value(inverse)?,
+        coordinates: coords,
+        success: true,
+        error: None,
+    })
+}
+
 fn solve_vacuum_equations(coords_json: &str, symmetry: &str, boundary_conditions_json: Option<&str>) -> Result<TensorResult, TensorError> {
     let coords: Vec<String> = serde_json::from_str(coords_json)?;
     
@@ -249,9 +657,8 @@ fn solve_vacuum_equations(coords_json: &str, symmetry: &str, boundary_conditions
     })
 }
 
-fn verify_solution(metric_json: &str, coords_json: &str, stress_energy_json: Option<&str>, lambda_json: Option<&str>) -> Result<TensorResult, TensorError> {
-    let metric: Vec<Vec<String>> = serde_json::from_str(metric_json)?;
-    let coords: Vec<String> = serde_json::from_str(coords_json)?;
+fn verify_solution(input: MetricInput, stress_energy_json: Option<&str>, lambda_json: Option<&str>) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = input;
     
     let parsed_metric = parse_metric_tensor(metric, &coords)?;
     
@@ -262,7 +669,7 @@ fn verify_solution(metric_json: &str, coords_json: &str, stress_energy_json: Opt
     };
     
     let lambda = if let Some(l_json) = lambda_json {
-        Some(SymbolicExpr::parse(l_json)?)
+        Some(SymbolicExpr::parse_strict(l_json)?)
     } else {
         None
     };
@@ -275,30 +682,31 @@ fn verify_solution(metric_json: &str, coords_json: &str, stress_energy_json: Opt
         constraints_satisfied: false,
         physical_parameters: HashMap::new(),
         solution_domain: "to be determined".to_string(),
+        metadata: SolutionMetadata::default(),
     };
     
-    let is_valid = verify_einstein_solution(&solution, stress_energy.as_ref(), lambda)?;
+    let verification = verify_einstein_solution(&solution, stress_energy.as_ref(), lambda)?;
     
     Ok(TensorResult {
         result_type: "solution_verification".to_string(),
-        data: serde_json::to_value(is_valid)?,
+        data: serde_json::to_value(verification)?,
         coordinates: coords,
         success: true,
         error: None,
     })
 }
 
-fn construct_field_equations(stress_energy_json: &str, coords_json: &str, lambda_json: Option<&str>) -> Result<TensorResult, TensorError> {
+fn construct_field_equations(stress_energy_json: &str, coords_json: &str, lambda_json: Option<&str>, gauge: Gauge) -> Result<TensorResult, TensorError> {
     let stress_energy: StressEnergyTensor = serde_json::from_str(stress_energy_json)?;
     let coords: Vec<String> = serde_json::from_str(coords_json)?;
     
     let lambda = if let Some(l_json) = lambda_json {
-        Some(SymbolicExpr::parse(l_json)?)
+        Some(SymbolicExpr::parse_strict(l_json)?)
     } else {
         None
     };
     
-    let equation_system = construct_einstein_field_equations(&stress_energy, &coords, lambda)?;
+    let equation_system = construct_einstein_field_equations(&stress_energy, &coords, lambda, gauge.name())?;
     
     Ok(TensorResult {
         result_type: "einstein_equations".to_string(),
@@ -308,3 +716,435 @@ fn construct_field_equations(stress_energy_json: &str, coords_json: &str, lambda
         error: None,
     })
 }
+
+/// One entry of a `batch` jobs file.
+#[derive(serde::Deserialize)]
+struct BatchJob {
+    #[serde(flatten)]
+    input: MetricInput,
+    operation: String,
+}
+
+/// Runs every job in a JSON array independently. A job that fails is recorded as an
+/// error entry and the remaining jobs still run.
+fn run_batch(jobs_json: &str) -> Result<Vec<TensorResult>, TensorError> {
+    let jobs: Vec<serde_json::Value> = serde_json::from_str(jobs_json)?;
+
+    Ok(jobs
+        .into_iter()
+        .map(|job| {
+            let coords = job.get("coords")
+                .and_then(|coords| serde_json::from_value::<Vec<String>>(coords.clone()).ok())
+                .unwrap_or_default();
+            let result = serde_json::from_value::<BatchJob>(job)
+                .map_err(TensorError::from)
+                .and_then(run_batch_job);
+
+            result.unwrap_or_else(|e| TensorResult {
+                result_type: "error".to_string(),
+                data: serde_json::Value::Null,
+                coordinates: coords,
+                success: false,
+                error: Some(e.to_string()),
+            })
+        })
+        .collect())
+}
+
+fn run_batch_job(job: BatchJob) -> Result<TensorResult, TensorError> {
+    let MetricInput { metric, coords } = job.input;
+    let metric = parse_metric_tensor(metric, &coords)?;
+
+    let (result_type, data) = match job.operation.as_str() {
+        "christoffel" => ("christoffel_symbols", serde_json::to_value(calculate_christoffel_symbols(&metric, &coords)?)?),
+        "riemann" => ("riemann_tensor", serde_json::to_value(calculate_riemann_tensor(&metric, &coords)?)?),
+        "ricci" => ("ricci_tensor", serde_json::to_value(calculate_ricci_tensor(&metric, &coords)?)?),
+        "ricci_scalar" => ("ricci_scalar", serde_json::to_value(calculate_ricci_scalar(&metric, &coords)?)?),
+        "einstein" => ("einstein_tensor", serde_json::to_value(calculate_einstein_tensor(&metric, &coords)?)?),
+        other => return Err(TensorError::ComputationError(format!("Unknown operation '{}'", other))),
+    };
+
+    Ok(TensorResult {
+        result_type: result_type.to_string(),
+        data,
+        coordinates: coords,
+        success: true,
+        error: None,
+    })
+}
+
+const REPL_HELP: &str = "\
+Commands:
+  coords [\"r\", \"theta\"]        set the active coordinates
+  let g = [[\"1\", \"0\"], ...]     define a metric
+  let x = <expr>               define a variable used in later expressions
+  christoffel <metric>         also: riemann, ricci, ricci_scalar, einstein
+  <expr>                       simplify an expression
+  help, quit";
+
+/// Metrics, variables and coordinates defined during a REPL session.
+#[derive(Default)]
+struct ReplContext {
+    coords: Vec<String>,
+    metrics: HashMap<String, MetricTensor>,
+    variables: HashMap<String, SymbolicExpr>,
+}
+
+fn run_repl<R: BufRead, W: Write>(input: R, output: &mut W) -> io::Result<()> {
+    let mut context = ReplContext::default();
+
+    write!(output, "tensor-calc> ")?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line == "quit" || line == "exit" {
+            break;
+        }
+
+        if !line.is_empty() {
+            match eval_repl_line(&mut context, line) {
+                Ok(text) => writeln!(output, "{}", text)?,
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        }
+
+        write!(output, "tensor-calc> ")?;
+        output.flush()?;
+    }
+
+    writeln!(output)
+}
+
+fn eval_repl_line(context: &mut ReplContext, line: &str) -> Result<String, TensorError> {
+    let (command, rest) = match line.split_once(char::is_whitespace) {
+        Some((command, rest)) => (command, rest.trim()),
+        None => (line, ""),
+    };
+
+    match command {
+        "help" => Ok(REPL_HELP.to_string()),
+        "coords" => {
+            if !rest.is_empty() {
+                context.coords = serde_json::from_str(rest)?;
+            }
+            Ok(format!("coords = {:?}", context.coords))
+        }
+        "let" => {
+            let (name, value) = rest.split_once('=').ok_or_else(|| {
+                TensorError::ComputationError("Expected `let <name> = <value>`".to_string())
+            })?;
+            let name = name.trim().to_string();
+            let value = value.trim();
+
+            if value.starts_with('[') {
+                let metric_strings: Vec<Vec<String>> = serde_json::from_str(value)?;
+                let metric = parse_metric_tensor(metric_strings, &context.coords)?;
+                let n = metric.len();
+                context.metrics.insert(name.clone(), metric);
+                Ok(format!("{} = {}x{} metric", name, n, n))
+            } else {
+                let expr = substitute_variables(&SymbolicExpr::parse_strict(value)?, &context.variables).simplify();
+                context.variables.insert(name.clone(), expr.clone());
+                Ok(format!("{} = {}", name, expr))
+            }
+        }
+        "christoffel" | "riemann" | "ricci" | "ricci_scalar" | "einstein" => {
+            let metric = context.metrics.get(rest).ok_or_else(|| {
+                TensorError::ComputationError(format!("Unknown metric '{}'", rest))
+            })?;
+            if metric.len() != context.coords.len() {
+                return Err(TensorError::InvalidMetric(format!(
+                    "Metric is {}x{} but {} coordinates are set",
+                    metric.len(), metric.len(), context.coords.len()
+                )));
+            }
+
+            let components = match command {
+                "christoffel" => calculate_christoffel_symbols(metric, &context.coords)?.symbols,
+                "riemann" => calculate_riemann_tensor(metric, &context.coords)?.components,
+                "ricci" => calculate_ricci_tensor(metric, &context.coords)?.components,
+                "ricci_scalar" => vec![calculate_ricci_scalar(metric, &context.coords)?],
+                _ => calculate_einstein_tensor(metric, &context.coords)?.components,
+            };
+
+            if components.is_empty() {
+                return Ok("all components are zero".to_string());
+            }
+
+            Ok(components
+                .iter()
+                .map(|component| {
+                    if component.indices.is_empty() {
+                        component.expression.clone()
+                    } else {
+                        format!("{:?} = {}", component.indices, component.expression)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        _ => {
+            let expr = SymbolicExpr::parse_strict(line)?;
+            Ok(substitute_variables(&expr, &context.variables).simplify().to_string())
+        }
+    }
+}
+
+fn substitute_variables(expr: &SymbolicExpr, variables: &HashMap<String, SymbolicExpr>) -> SymbolicExpr {
+    let sub = |e: &SymbolicExpr| Box::new(substitute_variables(e, variables));
+
+    match expr {
+        SymbolicExpr::Variable(name) => variables.get(name).cloned().unwrap_or_else(|| expr.clone()),
+        SymbolicExpr::Add(left, right) => SymbolicExpr::Add(sub(left), sub(right)),
+        SymbolicExpr::Subtract(left, right) => SymbolicExpr::Subtract(sub(left), sub(right)),
+        SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(sub(inner)),
+        SymbolicExpr::Multiply(left, right) => SymbolicExpr::Multiply(sub(left), sub(right)),
+        SymbolicExpr::Divide(left, right) => SymbolicExpr::Divide(sub(left), sub(right)),
+        SymbolicExpr::Power(base, exp) => SymbolicExpr::Power(sub(base), sub(exp)),
+        SymbolicExpr::Function(name, args) => SymbolicExpr::Function(
+            name.clone(),
+            args.iter().map(|arg| substitute_variables(arg, variables)).collect(),
+        ),
+        _ => expr.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_scripted_session() {
+        let script = "\
+coords [\"r\", \"theta\"]
+let g = [[\"1\", \"0\"], [\"0\", \"r^2\"]]
+christoffel g
+let k = 2
+k * 3 + 4
+ricci h
+quit
+k
+";
+        let mut output = Vec::new();
+        run_repl(io::Cursor::new(script), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("g = 2x2 metric"));
+        assert!(output.contains("[0, 1, 1] = "));
+        assert!(output.contains("k = 2"));
+        assert!(output.contains("> 10\n"));
+        assert!(output.contains("error: Computation error: Unknown metric 'h'"));
+        // Nothing after `quit` is evaluated
+        assert_eq!(output.matches("tensor-calc> ").count(), 7);
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn test_metric_file() {
+        let from_file = compute_christoffel_symbols(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        assert_eq!(from_file.coordinates, vec!["t", "r", "theta", "phi"]);
+
+        let args = MetricArgs {
+            metric: Some(r#"[["-(1 - 2*M/r)", "0", "0", "0"], ["0", "1/(1 - 2*M/r)", "0", "0"], ["0", "0", "r^2", "0"], ["0", "0", "0", "r^2 * sin(theta)^2"]]"#.to_string()),
+            diagonal: None,
+            coords: Some(r#"["t", "r", "theta", "phi"]"#.to_string()),
+            metric_file: None,
+        };
+        let inline = compute_christoffel_symbols(args.load().unwrap()).unwrap();
+        assert_eq!(from_file.data, inline.data);
+
+        let args = MetricArgs {
+            metric: None,
+            diagonal: Some(r#"["-(1 - 2*M/r)", "1/(1 - 2*M/r)", "r^2", "r^2 * sin(theta)^2"]"#.to_string()),
+            coords: Some(r#"["t", "r", "theta", "phi"]"#.to_string()),
+            metric_file: None,
+        };
+        let diagonal = compute_christoffel_symbols(args.load().unwrap()).unwrap();
+        assert_eq!(from_file.data, diagonal.data);
+
+        let args = MetricArgs { coords: Some(r#"["t", "r", "theta"]"#.to_string()), ..args };
+        assert!(matches!(args.load(), Err(TensorError::InvalidMetric(_))));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_metric_file() {
+        let json = compute_christoffel_symbols(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        let yaml = compute_christoffel_symbols(load_metric_file(&fixture("schwarzschild.yaml")).unwrap()).unwrap();
+
+        assert_eq!(json.coordinates, yaml.coordinates);
+        assert_eq!(json.data, yaml.data);
+    }
+
+    #[test]
+    fn test_render_latex() {
+        let polar = || MetricInput {
+            metric: vec![vec!["1".to_string(), "0".to_string()], vec!["0".to_string(), "r^2".to_string()]],
+            coords: vec!["r".to_string(), "theta".to_string()],
+        };
+
+        let christoffel = render_latex(&compute_christoffel_symbols(polar()).unwrap()).unwrap();
+        assert!(christoffel.contains(r"\Gamma^{r}_{\theta \theta} = -r"), "{}", christoffel);
+        assert!(christoffel.contains(r"\Gamma^{\theta}_{r \theta} = \frac{1}{r}"), "{}", christoffel);
+
+        let sphere = MetricInput {
+            metric: vec![vec!["1".to_string(), "0".to_string()], vec!["0".to_string(), "sin(theta)^2".to_string()]],
+            coords: vec!["theta".to_string(), "phi".to_string()],
+        };
+        let riemann = render_latex(&compute_riemann_tensor(sphere).unwrap()).unwrap();
+        assert!(riemann.contains(r"R^{\theta}_{\phi \theta \phi} = "), "{}", riemann);
+
+        assert!(render_latex(&compute_metric_inverse(polar()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_render_pretty() {
+        let polar = || MetricInput {
+            metric: vec![vec!["1".to_string(), "0".to_string()], vec!["0".to_string(), "r^2".to_string()]],
+            coords: vec!["r".to_string(), "theta".to_string()],
+        };
+
+        let christoffel = render_pretty(&compute_christoffel_symbols(polar()).unwrap(), false).unwrap();
+        assert!(christoffel.lines().any(|line| line.starts_with("Γ^{0}_{11} = ")), "{}", christoffel);
+
+        let scalar = render_pretty(&compute_ricci_scalar(polar()).unwrap(), false).unwrap();
+        assert!(scalar.starts_with("R = "), "{}", scalar);
+
+        // Streaming the Riemann components prints the same lines
+        let schwarzschild = || load_metric_file(&fixture("schwarzschild.json")).unwrap();
+        let mut streamed = Vec::new();
+        print_riemann_components(schwarzschild(), false, &mut streamed).unwrap();
+        let riemann = render_pretty(&compute_riemann_tensor(schwarzschild()).unwrap(), false).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap().trim_end(), riemann);
+    }
+
+    #[test]
+    fn test_render_unicode() {
+        let sphere = || MetricInput {
+            metric: vec![vec!["1".to_string(), "0".to_string()], vec!["0".to_string(), "sin(theta)^2".to_string()]],
+            coords: vec!["theta".to_string(), "phi".to_string()],
+        };
+
+        let christoffel = render_pretty(&compute_christoffel_symbols(sphere()).unwrap(), true).unwrap();
+        assert!(christoffel.contains("θ") && !christoffel.contains("theta"), "{}", christoffel);
+        let plain = render_pretty(&compute_christoffel_symbols(sphere()).unwrap(), false).unwrap();
+        assert_eq!(christoffel, plain.replace("theta", "θ"));
+
+        let mut streamed = Vec::new();
+        print_riemann_components(sphere(), true, &mut streamed).unwrap();
+        let riemann = render_pretty(&compute_riemann_tensor(sphere()).unwrap(), true).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap().trim_end(), riemann);
+    }
+
+    #[test]
+    fn test_kretschmann() {
+        let result = compute_kretschmann(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        assert_eq!(result.result_type, "kretschmann_scalar");
+
+        let scalar: TensorComponent = serde_json::from_value(result.data.clone()).unwrap();
+        let scalar = SymbolicExpr::parse(&scalar.expression).unwrap();
+        assert!(scalar.approx_eq(&SymbolicExpr::parse("48*M^2/r^6").unwrap()), "K = {}", scalar);
+        assert!(render_pretty(&result, false).unwrap().starts_with("K = "));
+    }
+
+    #[test]
+    fn test_metric_determinant() {
+        let result = compute_metric_determinant(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        assert_eq!(result.result_type, "metric_determinant");
+
+        let determinant: TensorComponent = serde_json::from_value(result.data.clone()).unwrap();
+        assert!(determinant.expression.contains("sin(theta)"), "{}", determinant.expression);
+        
+        // Like every other result, it takes --params
+        let numeric = substitute_params(result, r#"{"M": 1, "r": 2, "theta": 0}"#).unwrap();
+        let determinant: TensorComponent = serde_json::from_value(numeric.data).unwrap();
+        assert_eq!(determinant.expression, "0");
+    }
+
+    #[test]
+    fn test_substitute_params() {
+        let schwarzschild = || compute_christoffel_symbols(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        let component = |result: &TensorResult, indices: &[usize]| -> String {
+            let christoffel: ChristoffelResult = serde_json::from_value(result.data.clone()).unwrap();
+            christoffel.symbols.into_iter().find(|c| c.indices == indices).unwrap().expression
+        };
+
+        // Γ^t_tr = M/(r^2 - 2Mr) stays symbolic in r
+        let unit_mass = substitute_params(schwarzschild(), r#"{"M": 1}"#).unwrap();
+        let gamma_t_tr = SymbolicExpr::parse(&component(&unit_mass, &[0, 0, 1])).unwrap();
+        assert!(!gamma_t_tr.contains_variable("M"));
+        assert!(gamma_t_tr.approx_eq(&SymbolicExpr::parse("1/(r^2 - 2*r)").unwrap()), "{}", gamma_t_tr);
+
+        // ... and becomes a number once r is fixed too
+        let numeric = substitute_params(schwarzschild(), r#"{"M": 1, "r": 10}"#).unwrap();
+        assert_eq!(component(&numeric, &[0, 0, 1]), "0.0125");
+        
+        // At the horizon Γ^t_tr divides by zero; it is flagged, and the other
+        // components are still evaluated
+        let horizon = substitute_params(schwarzschild(), r#"{"M": 1, "r": 2}"#).unwrap();
+        let symbols = &horizon.data["symbols"];
+        let find = |indices: &[usize]| symbols.as_array().unwrap().iter()
+            .find(|c| c["indices"] == serde_json::json!(indices)).unwrap();
+        assert_eq!(find(&[0, 0, 1])["singular"], serde_json::json!(true));
+        assert!(SymbolicExpr::parse(find(&[0, 0, 1])["expression"].as_str().unwrap()).is_ok());
+        assert_eq!(component(&horizon, &[2, 1, 2]), "0.5");
+        assert!(find(&[2, 1, 2]).get("singular").is_none());
+
+        assert!(substitute_params(schwarzschild(), "[1]").is_err());
+    }
+
+    #[test]
+    fn test_add_expression_trees() {
+        let result = compute_christoffel_symbols(load_metric_file(&fixture("schwarzschild.json")).unwrap()).unwrap();
+        let result = add_expression_trees(result).unwrap();
+        let christoffel: Vec<TensorComponentTyped> = serde_json::from_value(result.data["symbols"].clone()).unwrap();
+        assert!(!christoffel.is_empty());
+        for component in christoffel {
+            assert_eq!(component.expression_tree, SymbolicExpr::parse(&component.expression).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_inverse_2x2() {
+        let args = MetricArgs {
+            metric: Some(r#"[["a", "b"], ["b", "c"]]"#.to_string()),
+            diagonal: None,
+            coords: Some(r#"["x", "y"]"#.to_string()),
+            metric_file: None,
+        };
+        let result = compute_metric_inverse(args.load().unwrap()).unwrap();
+        assert_eq!(result.result_type, "inverse_metric");
+
+        let inverse: Vec<Vec<String>> = serde_json::from_value(result.data).unwrap();
+        let expected = [["c/(a*c - b^2)", "-b/(a*c - b^2)"], ["-b/(a*c - b^2)", "a/(a*c - b^2)"]];
+        for i in 0..2 {
+            for j in 0..2 {
+                let actual = SymbolicExpr::parse(&inverse[i][j]).unwrap();
+                assert!(actual.approx_eq(&SymbolicExpr::parse(expected[i][j]).unwrap()), "g^{}{} = {}", i, j, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_continues_past_failures() {
+        let jobs = r#"[
+            {"metric": [["1", "x"], ["0", "1"]], "coords": ["x", "y"], "operation": "christoffel"},
+            {"metric": [["1", "0"], ["0", "r^2"]], "coords": ["r", "theta"], "operation": "ricci_scalar"}
+        ]"#;
+
+        let results = run_batch(jobs).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].success);
+        assert!(results[0].error.as_deref().unwrap().contains("symmetric"));
+        assert_eq!(results[0].coordinates, vec!["x", "y"]);
+        assert!(results[1].success);
+        assert_eq!(results[1].result_type, "ricci_scalar");
+    }
+}
@@ -0,0 +1,361 @@
+use crate::symbolic::SymbolicExpr;
+use crate::tensor::{calculate_metric_inverse, rank2_components, MetricTensor, RiemannResult};
+use crate::TensorError;
+
+/// A metric perturbation `g_μν = ḡ_μν + h_μν` about a background `ḡ`, for working with
+/// gravitational waves at first order in `h`.
+///
+/// The linearized curvature is only implemented about backgrounds whose components
+/// are constant in the chosen coordinates, such as Minkowski space in Cartesian form.
+#[derive(Debug, Clone)]
+pub struct Perturbation {
+    pub background: MetricTensor,
+    pub h: MetricTensor,
+}
+
+impl Perturbation {
+    pub fn new(background: MetricTensor, h: MetricTensor) -> Result<Self, TensorError> {
+        let n = background.len();
+        if background.iter().any(|row| row.len() != n) {
+            return Err(TensorError::InvalidMetric("Background metric must be square".to_string()));
+        }
+        if h.len() != n || h.iter().any(|row| row.len() != n) {
+            return Err(TensorError::InvalidMetric(format!(
+                "Perturbation must be {}x{} to match the background metric",
+                n, n
+            )));
+        }
+
+        Ok(Perturbation { background, h })
+    }
+
+    /// Linearized Ricci tensor
+    /// `R_μν = ½(∂_ρ∂_μ h^ρ_ν + ∂_ρ∂_ν h^ρ_μ - □h_μν - ∂_μ∂_ν h)`.
+    #[allow(clippy::needless_range_loop)]
+    pub fn ricci_tensor(&self, coords: &[String]) -> Result<MetricTensor, TensorError> {
+        let n = self.background.len();
+        if coords.len() != n {
+            return Err(TensorError::ComputationError(format!(
+                "Expected {} coordinates but got {}",
+                n, coords.len()
+            )));
+        }
+        self.check_constant_background(coords)?;
+        let inverse = calculate_metric_inverse(&self.background)?;
+
+        // h^ρ_ν = ḡ^ρσ h_σν
+        let mut mixed = vec![vec![SymbolicExpr::Zero; n]; n];
+        for rho in 0..n {
+            for nu in 0..n {
+                let mut expr = SymbolicExpr::Zero;
+                for sigma in 0..n {
+                    expr = SymbolicExpr::Add(
+                        Box::new(expr),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(inverse[rho][sigma].clone()),
+                            Box::new(self.h[sigma][nu].clone()),
+                        )),
+                    );
+                }
+                mixed[rho][nu] = expr.simplify();
+            }
+        }
+
+        let trace = self.trace_with(&inverse);
+
+        let mut ricci = vec![vec![SymbolicExpr::Zero; n]; n];
+        for mu in 0..n {
+            for nu in 0..n {
+                let mut expr = SymbolicExpr::Zero;
+
+                // ∂_ρ∂_μ h^ρ_ν + ∂_ρ∂_ν h^ρ_μ
+                for rho in 0..n {
+                    expr = SymbolicExpr::Add(
+                        Box::new(expr),
+                        Box::new(SymbolicExpr::Add(
+                            Box::new(mixed[rho][nu].derivative(&coords[rho]).derivative(&coords[mu])),
+                            Box::new(mixed[rho][mu].derivative(&coords[rho]).derivative(&coords[nu])),
+                        )),
+                    );
+                }
+
+                // □h_μν = ḡ^ρσ ∂_ρ∂_σ h_μν
+                for rho in 0..n {
+                    for sigma in 0..n {
+                        if inverse[rho][sigma].is_zero() {
+                            continue;
+                        }
+                        expr = SymbolicExpr::Subtract(
+                            Box::new(expr),
+                            Box::new(SymbolicExpr::Multiply(
+                                Box::new(inverse[rho][sigma].clone()),
+                                Box::new(self.h[mu][nu].derivative(&coords[rho]).derivative(&coords[sigma])),
+                            )),
+                        );
+                    }
+                }
+
+                // ∂_μ∂_ν h
+                expr = SymbolicExpr::Subtract(
+                    Box::new(expr),
+                    Box::new(trace.derivative(&coords[mu]).derivative(&coords[nu])),
+                );
+
+                ricci[mu][nu] = SymbolicExpr::Multiply(
+                    Box::new(SymbolicExpr::Constant(0.5)),
+                    Box::new(expr),
+                ).simplify();
+            }
+        }
+
+        Ok(ricci)
+    }
+
+    /// Linearized Einstein tensor `G_μν = R_μν - ½ ḡ_μν R`.
+    pub fn einstein_tensor(&self, coords: &[String]) -> Result<MetricTensor, TensorError> {
+        let n = self.background.len();
+        let ricci = self.ricci_tensor(coords)?;
+        let inverse = calculate_metric_inverse(&self.background)?;
+
+        let mut scalar = SymbolicExpr::Zero;
+        for mu in 0..n {
+            for nu in 0..n {
+                scalar = SymbolicExpr::Add(
+                    Box::new(scalar),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(inverse[mu][nu].clone()),
+                        Box::new(ricci[mu][nu].clone()),
+                    )),
+                );
+            }
+        }
+        let scalar = scalar.simplify();
+
+        let mut einstein = vec![vec![SymbolicExpr::Zero; n]; n];
+        for mu in 0..n {
+            for nu in 0..n {
+                einstein[mu][nu] = SymbolicExpr::Subtract(
+                    Box::new(ricci[mu][nu].clone()),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(SymbolicExpr::Constant(0.5)),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(self.background[mu][nu].clone()),
+                            Box::new(scalar.clone()),
+                        )),
+                    )),
+                ).simplify();
+            }
+        }
+
+        Ok(einstein)
+    }
+
+    /// Projects `h` onto transverse-traceless gauge for a plane wave travelling along the
+    /// coordinate at index `direction`. The first coordinate is taken to be time, so the
+    /// components along time and the propagation direction are dropped and the trace is
+    /// removed from the transverse block.
+    pub fn transverse_traceless(&self, direction: usize) -> Result<Perturbation, TensorError> {
+        let n = self.background.len();
+        if direction == 0 || direction >= n {
+            return Err(TensorError::ComputationError(format!(
+                "Propagation direction must be a spatial coordinate index in 1..{}, got {}",
+                n, direction
+            )));
+        }
+        let inverse = calculate_metric_inverse(&self.background)?;
+        let transverse: Vec<usize> = (1..n).filter(|&i| i != direction).collect();
+
+        // Trace over the transverse block only
+        let mut trace = SymbolicExpr::Zero;
+        for &i in &transverse {
+            for &j in &transverse {
+                trace = SymbolicExpr::Add(
+                    Box::new(trace),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(inverse[i][j].clone()),
+                        Box::new(self.h[i][j].clone()),
+                    )),
+                );
+            }
+        }
+        let trace = trace.simplify();
+
+        let mut h = vec![vec![SymbolicExpr::Zero; n]; n];
+        for &i in &transverse {
+            for &j in &transverse {
+                h[i][j] = SymbolicExpr::Subtract(
+                    Box::new(self.h[i][j].clone()),
+                    Box::new(SymbolicExpr::Divide(
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(self.background[i][j].clone()),
+                            Box::new(trace.clone()),
+                        )),
+                        Box::new(SymbolicExpr::Constant(transverse.len() as f64)),
+                    )),
+                ).simplify();
+            }
+        }
+
+        Ok(Perturbation {
+            background: self.background.clone(),
+            h,
+        })
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn trace_with(&self, inverse: &MetricTensor) -> SymbolicExpr {
+        let n = self.background.len();
+        let mut trace = SymbolicExpr::Zero;
+        for mu in 0..n {
+            for nu in 0..n {
+                trace = SymbolicExpr::Add(
+                    Box::new(trace),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(inverse[mu][nu].clone()),
+                        Box::new(self.h[mu][nu].clone()),
+                    )),
+                );
+            }
+        }
+        trace.simplify()
+    }
+
+    fn check_constant_background(&self, coords: &[String]) -> Result<(), TensorError> {
+        for row in &self.background {
+            for component in row {
+                if coords.iter().any(|coord| !component.derivative(coord).simplify().is_zero()) {
+                    return Err(TensorError::ComputationError(format!(
+                        "Linearization requires a background with constant components, but found '{}'",
+                        component
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Linearized Einstein tensor of a perturbation `h_μν` about Minkowski space
+/// `η = diag(-1, 1, ..., 1)`, with the first coordinate taken as time. Only terms
+/// first order in `h` are kept, which is far cheaper than the full computation on
+/// `η + h`.
+pub fn calculate_einstein_tensor_linearized(
+    perturbation: &MetricTensor,
+    coords: &[String],
+) -> Result<RiemannResult, TensorError> {
+    let n = perturbation.len();
+    let minkowski = (0..n)
+        .map(|i| (0..n).map(|j| match (i, j) {
+            (0, 0) => SymbolicExpr::Constant(-1.0),
+            (i, j) if i == j => SymbolicExpr::One,
+            _ => SymbolicExpr::Zero,
+        }).collect())
+        .collect();
+    let einstein: MetricTensor = Perturbation::new(minkowski, perturbation.clone())?
+        .einstein_tensor(coords)?
+        .iter()
+        .map(|row| row.iter().map(|component| component.simplify_expanded()).collect())
+        .collect();
+
+    Ok(RiemannResult {
+        components: rank2_components(&einstein, false),
+        dimension: n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::parse_metric_tensor;
+
+    fn minkowski(coords: &[String]) -> MetricTensor {
+        let rows: Vec<Vec<String>> = [
+            ["-1", "0", "0", "0"],
+            ["0", "1", "0", "0"],
+            ["0", "0", "1", "0"],
+            ["0", "0", "0", "1"],
+        ].iter().map(|row| row.iter().map(|s| s.to_string()).collect()).collect();
+        parse_metric_tensor(rows, coords).unwrap()
+    }
+
+    #[test]
+    fn test_plane_wave_einstein_tensor() {
+        let coords: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let wave = SymbolicExpr::parse("cos(w*t - k*z)").unwrap();
+        let mut h = vec![vec![SymbolicExpr::Zero; 4]; 4];
+        h[1][1] = wave.clone();
+        h[2][2] = SymbolicExpr::parse("-cos(w*t - k*z)").unwrap();
+
+        let perturbation = Perturbation::new(minkowski(&coords), h).unwrap();
+        let einstein = perturbation.einstein_tensor(&coords).unwrap();
+
+        // G_μν = -½ □h_μν in TT gauge, with □cos(wt - kz) = (w^2 - k^2) cos(wt - kz)
+        crate::assert_expr_eq!(einstein[1][1], SymbolicExpr::parse("-0.5 * (w^2 - k^2) * cos(w*t - k*z)").unwrap());
+        crate::assert_expr_eq!(einstein[2][2], SymbolicExpr::parse("0.5 * (w^2 - k^2) * cos(w*t - k*z)").unwrap());
+        crate::assert_expr_eq!(einstein[0][0], SymbolicExpr::Zero);
+        crate::assert_expr_eq!(einstein[1][2], SymbolicExpr::Zero);
+    }
+
+    #[test]
+    fn test_linearized_einstein_tensor() {
+        let coords: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let h = parse_metric_tensor(vec![
+            vec!["0".into(), "0".into(), "0".into(), "0".into()],
+            vec!["0".into(), "cos(w*t - k*z)".into(), "sin(w*t - k*z)".into(), "0".into()],
+            vec!["0".into(), "sin(w*t - k*z)".into(), "-cos(w*t - k*z)".into(), "0".into()],
+            vec!["0".into(), "0".into(), "0".into(), "0".into()],
+        ], &coords).unwrap();
+
+        // Transverse-traceless h leaves only the wave operator: G_μν = -½ □h_μν
+        let einstein = calculate_einstein_tensor_linearized(&h, &coords).unwrap();
+        assert_eq!(einstein.dimension, 4);
+        let component = |indices: [usize; 2]| {
+            einstein.components.iter()
+                .find(|c| c.indices == indices)
+                .map(|c| SymbolicExpr::parse(&c.expression).unwrap())
+                .unwrap_or(SymbolicExpr::Zero)
+        };
+        crate::assert_expr_eq!(component([1, 1]), SymbolicExpr::parse("-0.5 * (w^2 - k^2) * cos(w*t - k*z)").unwrap());
+        crate::assert_expr_eq!(component([1, 2]), SymbolicExpr::parse("-0.5 * (w^2 - k^2) * sin(w*t - k*z)").unwrap());
+        crate::assert_expr_eq!(component([2, 2]), SymbolicExpr::parse("0.5 * (w^2 - k^2) * cos(w*t - k*z)").unwrap());
+        assert!(einstein.components.iter().all(|c| c.indices[0] != 0 && c.indices[0] != 3), "{:?}", einstein.components);
+
+        // The same as the first-order term of the full Einstein tensor of η + εh
+        let full_metric: MetricTensor = minkowski(&coords).iter().zip(&h)
+            .map(|(background, h)| background.iter().zip(h)
+                .map(|(b, h)| SymbolicExpr::Add(
+                    Box::new(b.clone()),
+                    Box::new(SymbolicExpr::Multiply(Box::new(SymbolicExpr::Variable("eps".to_string())), Box::new(h.clone()))),
+                ).simplify())
+                .collect())
+            .collect();
+        let full = crate::tensor::calculate_einstein_tensor(&full_metric, &coords).unwrap();
+        let full_11 = full.components.iter().find(|c| c.indices == [1, 1]).unwrap();
+        let first_order = SymbolicExpr::parse(&full_11.expression).unwrap()
+            .series("eps", 0.0, 1).unwrap()
+            .derivative("eps");
+        crate::assert_expr_eq!(first_order, component([1, 1]));
+    }
+
+    #[test]
+    fn test_transverse_traceless_projection() {
+        let coords: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let h = parse_metric_tensor(vec![
+            vec!["a".into(), "b".into(), "0".into(), "0".into()],
+            vec!["b".into(), "p + q".into(), "c".into(), "0".into()],
+            vec!["0".into(), "c".into(), "p - q".into(), "0".into()],
+            vec!["0".into(), "0".into(), "0".into(), "a".into()],
+        ], &coords).unwrap();
+        let perturbation = Perturbation::new(minkowski(&coords), h).unwrap();
+
+        let tt = perturbation.transverse_traceless(3).unwrap();
+
+        crate::assert_expr_eq!(tt.h[1][1], SymbolicExpr::Variable("q".to_string()));
+        crate::assert_expr_eq!(tt.h[2][2], SymbolicExpr::parse("-q").unwrap());
+        crate::assert_expr_eq!(tt.h[1][2], SymbolicExpr::Variable("c".to_string()));
+        assert!(tt.h[0][0].is_zero() && tt.h[0][1].is_zero() && tt.h[3][3].is_zero());
+
+        let short = vec![vec![SymbolicExpr::Zero; 3]; 3];
+        assert!(Perturbation::new(minkowski(&coords), short).is_err());
+    }
+}
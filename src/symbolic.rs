@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
-use regex::Regex;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use num_complex::Complex64;
 use crate::TensorError;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,6 +11,7 @@ pub enum SymbolicExpr {
     Constant(f64),
     Add(Box<SymbolicExpr>, Box<SymbolicExpr>),
     Subtract(Box<SymbolicExpr>, Box<SymbolicExpr>),
+    Negate(Box<SymbolicExpr>),
     Multiply(Box<SymbolicExpr>, Box<SymbolicExpr>),
     Divide(Box<SymbolicExpr>, Box<SymbolicExpr>),
     Power(Box<SymbolicExpr>, Box<SymbolicExpr>),
@@ -30,9 +33,14 @@ impl fmt::Display for SymbolicExpr {
             }
             SymbolicExpr::Add(left, right) => write!(f, "({} + {})", left, right),
             SymbolicExpr::Subtract(left, right) => write!(f, "({} - {})", left, right),
+            SymbolicExpr::Negate(inner) => write!(f, "-{}", inner),
             SymbolicExpr::Multiply(left, right) => write!(f, "({} * {})", left, right),
             SymbolicExpr::Divide(left, right) => write!(f, "({} / {})", left, right),
-            SymbolicExpr::Power(base, exp) => write!(f, "{}^{}", base, exp),
+            SymbolicExpr::Power(base, exp) => match **base {
+                SymbolicExpr::Power(_, _) | SymbolicExpr::Negate(_) => write!(f, "({})^{}", base, exp),
+                SymbolicExpr::Constant(val) if val < 0.0 => write!(f, "({})^{}", base, exp),
+                _ => write!(f, "{}^{}", base, exp),
+            },
             SymbolicExpr::Function(name, args) => {
                 if args.is_empty() {
                     write!(f, "{}()", name)
@@ -49,132 +57,516 @@ impl fmt::Display for SymbolicExpr {
     }
 }
 
+/// Which rules `SymbolicExpr::simplify_with` applies, and how hard it tries. The
+/// default is the preset used by `simplify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimplifyOptions {
+    /// Multiply out products and powers of sums after simplifying, keeping the
+    /// expanded form only when it isn't bigger, as `simplify_expanded` does.
+    pub expand: bool,
+    /// Use `sin(x)^2 + cos(x)^2 = 1` when collecting terms.
+    pub trig_identities: bool,
+    /// Cancel factors shared by a numerator and denominator, like `(a*b)/(a*c)`
+    /// or `x^a / x^b`.
+    pub cancel_factors: bool,
+    /// Upper bound on bottom-up passes. Passes stop at a fixed point, where a pass
+    /// leaves the expression unchanged, but some rules only reorder or regroup the
+    /// tree rather than shrink it, so this cap is what guarantees termination.
+    pub max_iterations: usize,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        SimplifyOptions {
+            expand: false,
+            trig_identities: true,
+            cancel_factors: true,
+            max_iterations: 16,
+        }
+    }
+}
+
+/// Options controlling how expressions are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Return a `ParseError` for input the grammar doesn't accept, instead of keeping
+    /// it as an opaque variable named after the whole input.
+    pub strict: bool,
+}
+
 impl SymbolicExpr {
+    /// Lenient parse: empty input is zero, and anything the grammar can't handle is
+    /// kept as an opaque variable. Prefer `parse_strict` for user-supplied input.
     pub fn parse(input: &str) -> Result<Self, TensorError> {
+        Self::parse_with_options(input, &ParseOptions::default())
+    }
+
+    /// Parses `input`, rejecting empty or malformed expressions such as `1 +`.
+    pub fn parse_strict(input: &str) -> Result<Self, TensorError> {
+        Self::parse_with_options(input, &ParseOptions { strict: true })
+    }
+
+    pub fn parse_with_options(input: &str, options: &ParseOptions) -> Result<Self, TensorError> {
         let input = input.trim();
         
         if input.is_empty() {
+            if options.strict {
+                return Err(TensorError::ParseError("Empty expression".to_string()));
+            }
             return Ok(SymbolicExpr::Zero);
         }
         
-        // Handle special cases first
-        match input {
-            "0" => return Ok(SymbolicExpr::Zero),
-            "1" => return Ok(SymbolicExpr::One),
-            _ => {}
-        }
-        
-        // Handle constants
-        if let Ok(val) = input.parse::<f64>() {
-            return Ok(SymbolicExpr::Constant(val));
+        match Parser::new(input).and_then(|mut parser| parser.parse_all()) {
+            Ok(expr) => Ok(expr),
+            Err(e) if options.strict => Err(e),
+            // Anything the grammar can't handle is kept as an opaque variable
+            Err(_) => Ok(SymbolicExpr::Variable(input.to_string())),
         }
-        
-        // Handle simple variables
-        if input.chars().all(|c| c.is_alphanumeric() || c == '_') {
-            return Ok(SymbolicExpr::Variable(input.to_string()));
-        }
-        
-        // Handle powers (r^2, etc.)
-        if let Some(captures) = Regex::new(r"^([a-zA-Z_][a-zA-Z0-9_]*)\^(\d+)$").unwrap().captures(input) {
-            let base = SymbolicExpr::Variable(captures.get(1).unwrap().as_str().to_string());
-            let exp = SymbolicExpr::Constant(captures.get(2).unwrap().as_str().parse::<f64>().unwrap());
-            return Ok(SymbolicExpr::Power(Box::new(base), Box::new(exp)));
-        }
-        
-        // Handle functions like sin(x), cos(theta), etc.
-        if let Some(captures) = Regex::new(r"^([a-zA-Z_][a-zA-Z0-9_]*)\(([^)]*)\)$").unwrap().captures(input) {
-            let func_name = captures.get(1).unwrap().as_str().to_string();
-            let args_str = captures.get(2).unwrap().as_str();
-            
-            let mut args = Vec::new();
-            if !args_str.is_empty() {
-                for arg in args_str.split(',') {
-                    args.push(Self::parse(arg.trim())?);
-                }
+    }
+
+    /// Applies algebraic simplification rules bottom-up until the expression stops
+    /// changing. Divisions by zero are never folded: they stay symbolic, and `eval`
+    /// reports them as errors.
+    pub fn simplify(&self) -> Self {
+        self.simplify_with(SimplifyOptions::default())
+    }
+
+    /// `simplify` with the rules and effort chosen by `opts`. Passes stop early once
+    /// the expression stops changing; with `opts.expand` the result is then expanded
+    /// as in `simplify_expanded`.
+    pub fn simplify_with(&self, opts: SimplifyOptions) -> Self {
+        let mut current = self.clone();
+        for _ in 0..opts.max_iterations {
+            let next = current.simplify_once(&opts);
+            if next == current {
+                break;
             }
-            
-            return Ok(SymbolicExpr::Function(func_name, args));
+            current = next;
+        }
+        if opts.expand {
+            current.expanded(&opts)
+        } else {
+            current
         }
-        
-        // For now, treat complex expressions as variables
-        // In a more complete implementation, we'd parse arithmetic expressions
-        Ok(SymbolicExpr::Variable(input.to_string()))
     }
 
-    pub fn simplify(&self) -> Self {
+    fn simplify_once(&self, opts: &SimplifyOptions) -> Self {
         match self {
             SymbolicExpr::Add(left, right) => {
-                let left = left.simplify();
-                let right = right.simplify();
+                let left = left.simplify_once(opts);
+                let right = right.simplify_once(opts);
                 
                 match (&left, &right) {
                     (SymbolicExpr::Zero, expr) | (expr, SymbolicExpr::Zero) => expr.clone(),
                     (SymbolicExpr::Constant(a), SymbolicExpr::Constant(b)) => {
                         SymbolicExpr::Constant(a + b)
                     }
-                    _ => SymbolicExpr::Add(Box::new(left), Box::new(right)),
+                    // a + (-b) = a - b and (-a) + b = b - a
+                    (expr, SymbolicExpr::Negate(inner)) | (SymbolicExpr::Negate(inner), expr) => {
+                        SymbolicExpr::Subtract(Box::new(expr.clone()), inner.clone()).collect_like_terms(opts)
+                    }
+                    _ => SymbolicExpr::Add(Box::new(left), Box::new(right)).collect_like_terms(opts),
                 }
             }
             SymbolicExpr::Subtract(left, right) => {
-                let left = left.simplify();
-                let right = right.simplify();
+                let left = left.simplify_once(opts);
+                let right = right.simplify_once(opts);
                 
                 match (&left, &right) {
                     (expr, SymbolicExpr::Zero) => expr.clone(),
-                    (SymbolicExpr::Zero, expr) => {
-                        SymbolicExpr::Subtract(Box::new(SymbolicExpr::Zero), Box::new(expr.clone()))
+                    (a, b) if a == b => SymbolicExpr::Zero,
+                    (SymbolicExpr::Zero, expr) => SymbolicExpr::Negate(Box::new(expr.clone())).simplify_once(opts),
+                    // a - (-b) = a + b
+                    (expr, SymbolicExpr::Negate(inner)) => {
+                        SymbolicExpr::Add(Box::new(expr.clone()), inner.clone()).collect_like_terms(opts)
                     }
                     (SymbolicExpr::Constant(a), SymbolicExpr::Constant(b)) => {
                         SymbolicExpr::Constant(a - b)
                     }
-                    _ => SymbolicExpr::Subtract(Box::new(left), Box::new(right)),
+                    // a - (-2) = a + 2 and a - (-2 * b) = a + 2 * b
+                    (expr, term) if term.coefficient().is_some_and(|(c, _)| c < 0.0) => {
+                        let (c, rest) = term.coefficient().unwrap();
+                        let positive = match rest {
+                            Some(rest) if c == -1.0 => rest,
+                            Some(rest) => SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(-c)), Box::new(rest)),
+                            None => SymbolicExpr::Constant(-c),
+                        };
+                        SymbolicExpr::Add(Box::new(expr.clone()), Box::new(positive)).collect_like_terms(opts)
+                    }
+                    _ => SymbolicExpr::Subtract(Box::new(left), Box::new(right)).collect_like_terms(opts),
                 }
             }
+            SymbolicExpr::Negate(inner) => match inner.simplify_once(opts) {
+                SymbolicExpr::Negate(inner) => *inner,
+                SymbolicExpr::Zero => SymbolicExpr::Zero,
+                SymbolicExpr::Constant(c) => SymbolicExpr::Constant(-c),
+                SymbolicExpr::One => SymbolicExpr::Constant(-1.0),
+                // -(-2 * x) = 2 * x
+                SymbolicExpr::Multiply(a, b) if a.constant_value().is_some_and(|c| c < 0.0) => {
+                    SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(-a.constant_value().unwrap())), b)
+                }
+                inner => SymbolicExpr::Negate(Box::new(inner)),
+            },
             SymbolicExpr::Multiply(left, right) => {
-                let left = left.simplify();
-                let right = right.simplify();
+                let left = left.simplify_once(opts);
+                let right = right.simplify_once(opts);
                 
                 match (&left, &right) {
                     (SymbolicExpr::Zero, _) | (_, SymbolicExpr::Zero) => SymbolicExpr::Zero,
-                    (SymbolicExpr::One, expr) | (expr, SymbolicExpr::One) => expr.clone(),
+                    (unit, expr) | (expr, unit) if unit.constant_value() == Some(1.0) => expr.clone(),
                     (SymbolicExpr::Constant(a), SymbolicExpr::Constant(b)) => {
                         SymbolicExpr::Constant(a * b)
                     }
+                    // Move factors into a quotient so they can cancel: a * (b / c) = (a * b) / c
+                    (factor, SymbolicExpr::Divide(n, d)) | (SymbolicExpr::Divide(n, d), factor) if !d.is_zero() => {
+                        SymbolicExpr::Divide(
+                            Box::new(SymbolicExpr::Multiply(Box::new(factor.clone()), n.clone())),
+                            d.clone(),
+                        )
+                    }
+                    // Fold coefficients through nested products: 2 * (3 * x) = 6 * x
+                    (l, r) if l.coefficient().is_some() && r.coefficient().is_some() => {
+                        let (a, x) = l.coefficient().unwrap();
+                        let (b, y) = r.coefficient().unwrap();
+                        let product = match (x, y) {
+                            (Some(x), Some(y)) => SymbolicExpr::Multiply(Box::new(x), Box::new(y)),
+                            (Some(x), None) | (None, Some(x)) => x,
+                            (None, None) => SymbolicExpr::One,
+                        };
+                        SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(a * b)), Box::new(product))
+                    }
+                    // x^a * x^b = x^(a + b)
+                    (l, r) if power_operands(l, r).is_some() => {
+                        let (base, a, b) = power_operands(l, r).unwrap();
+                        SymbolicExpr::Power(
+                            Box::new(base),
+                            Box::new(SymbolicExpr::Add(Box::new(a), Box::new(b)).simplify_once(opts)),
+                        )
+                    }
+                    // sqrt(a) * sqrt(a) = a
+                    (SymbolicExpr::Function(f, a), SymbolicExpr::Function(g, b))
+                        if f == "sqrt" && g == "sqrt" && a.len() == 1 && a == b =>
+                    {
+                        a[0].clone()
+                    }
+                    // sqrt(a) * sqrt(b) = sqrt(a * b)
+                    (SymbolicExpr::Function(f, a), SymbolicExpr::Function(g, b))
+                        if f == "sqrt" && g == "sqrt" && a.len() == 1 && b.len() == 1 =>
+                    {
+                        SymbolicExpr::Function("sqrt".to_string(), vec![
+                            SymbolicExpr::Multiply(Box::new(a[0].clone()), Box::new(b[0].clone())).simplify_once(opts),
+                        ])
+                    }
                     _ => SymbolicExpr::Multiply(Box::new(left), Box::new(right)),
                 }
             }
             SymbolicExpr::Divide(left, right) => {
-                let left = left.simplify();
-                let right = right.simplify();
+                let left = left.simplify_once(opts);
+                let right = right.simplify_once(opts);
                 
                 match (&left, &right) {
+                    // Division by zero is left symbolic; `eval` reports it as an error
+                    (_, denominator) if denominator.is_zero() => {
+                        SymbolicExpr::Divide(Box::new(left), Box::new(right))
+                    }
                     (SymbolicExpr::Zero, _) => SymbolicExpr::Zero,
-                    (expr, SymbolicExpr::One) => expr.clone(),
-                    (SymbolicExpr::Constant(a), SymbolicExpr::Constant(b)) if *b != 0.0 => {
-                        SymbolicExpr::Constant(a / b)
+                    (expr, unit) if unit.constant_value() == Some(1.0) => expr.clone(),
+                    (numerator, denominator) if numerator == denominator => SymbolicExpr::One,
+                    (numerator, denominator) if numerator.constant_value().is_some()
+                        && denominator.constant_value().is_some() =>
+                    {
+                        SymbolicExpr::Constant(numerator.constant_value().unwrap() / denominator.constant_value().unwrap())
                     }
+                    // x / (a/b) = (x*b) / a
+                    (numerator, SymbolicExpr::Divide(a, b)) if !a.is_zero() => SymbolicExpr::Divide(
+                        Box::new(SymbolicExpr::Multiply(Box::new(numerator.clone()), b.clone())),
+                        a.clone(),
+                    ),
+                    (numerator, denominator) if opts.cancel_factors => cancel_common_factors(numerator, denominator)
+                        // x^a / x^b = x^(a - b) for symbolic exponents
+                        .or_else(|| power_operands(numerator, denominator).map(|(base, a, b)| SymbolicExpr::Power(
+                            Box::new(base),
+                            Box::new(SymbolicExpr::Subtract(Box::new(a), Box::new(b)).simplify_once(opts)),
+                        )))
+                        .unwrap_or_else(|| SymbolicExpr::Divide(Box::new(left), Box::new(right))),
                     _ => SymbolicExpr::Divide(Box::new(left), Box::new(right)),
                 }
             }
             SymbolicExpr::Power(base, exp) => {
-                let base = base.simplify();
-                let exp = exp.simplify();
+                let base = base.simplify_once(opts);
+                let exp = exp.simplify_once(opts);
                 
                 match (&base, &exp) {
-                    (_, SymbolicExpr::Zero) => SymbolicExpr::One,
-                    (expr, SymbolicExpr::One) => expr.clone(),
-                    (SymbolicExpr::Zero, _) => SymbolicExpr::Zero,
+                    (_, exp) if exp.is_zero() => SymbolicExpr::One,
+                    (expr, exp) if exp.constant_value() == Some(1.0) => expr.clone(),
+                    // 0^n = 0 only for n > 0; a negative or unknown exponent may be a
+                    // division by zero, which is left symbolic like `x/0`
+                    (base, exp) if base.is_zero() => match exp.constant_value() {
+                        Some(n) if n > 0.0 => SymbolicExpr::Zero,
+                        _ => SymbolicExpr::Power(Box::new(base.clone()), Box::new(exp.clone())),
+                    },
                     (SymbolicExpr::One, _) => SymbolicExpr::One,
                     (SymbolicExpr::Constant(a), SymbolicExpr::Constant(b)) => {
                         SymbolicExpr::Constant(a.powf(*b))
                     }
+                    // x^(1/2) = sqrt(x)
+                    (expr, exp) if exp.constant_value() == Some(0.5) => {
+                        SymbolicExpr::Function("sqrt".to_string(), vec![expr.clone()])
+                    }
+                    // (x^a)^n = x^(a*n), which holds for any x only when n is an integer
+                    (SymbolicExpr::Power(inner, a), n) if n.constant_value().is_some_and(|n| n.fract() == 0.0) => {
+                        SymbolicExpr::Power(
+                            inner.clone(),
+                            Box::new(SymbolicExpr::Multiply(a.clone(), Box::new(n.clone())).simplify_once(opts)),
+                        )
+                    }
+                    // sqrt(x)^2 = x and cbrt(x)^3 = x
+                    (SymbolicExpr::Function(name, args), exp) if args.len() == 1
+                        && ((name == "sqrt" && exp.constant_value() == Some(2.0))
+                            || (name == "cbrt" && exp.constant_value() == Some(3.0))) =>
+                    {
+                        args[0].clone()
+                    }
                     _ => SymbolicExpr::Power(Box::new(base), Box::new(exp)),
                 }
             }
+            SymbolicExpr::Function(name, args) => {
+                let args: Vec<SymbolicExpr> = args.iter().map(|arg| arg.simplify_once(opts)).collect();
+                
+                match (name.as_str(), args.as_slice()) {
+                    ("sqrt", [arg]) if arg.is_zero() => SymbolicExpr::Zero,
+                    ("sqrt", [arg]) if arg.constant_value() == Some(1.0) => SymbolicExpr::One,
+                    // Perfect squares: sqrt(9) = 3
+                    ("sqrt", [arg]) if arg.constant_value().is_some_and(|c| c > 0.0 && c.sqrt().fract() == 0.0) => {
+                        SymbolicExpr::Constant(arg.constant_value().unwrap().sqrt())
+                    }
+                    // sqrt(x^2) = abs(x)
+                    ("sqrt", [SymbolicExpr::Power(base, exp)]) if exp.constant_value() == Some(2.0) => {
+                        SymbolicExpr::Function("abs".to_string(), vec![(**base).clone()])
+                    }
+                    _ => SymbolicExpr::Function(name.clone(), args),
+                }
+            }
             _ => self.clone(),
         }
     }
 
+    /// Combines like terms of an already simplified sum, so `x + x` becomes `2 * x`
+    /// and `3*r - r` becomes `2 * r`. Terms are matched structurally after their
+    /// numeric coefficient is split off. Sums with nothing to combine are returned
+    /// unchanged, keeping their original shape.
+    fn collect_like_terms(self, opts: &SimplifyOptions) -> Self {
+        let mut terms = Vec::new();
+        self.additive_terms(1.0, &mut terms);
+        
+        let mut collected: Vec<(f64, SymbolicExpr)> = Vec::new();
+        for (coefficient, term) in terms {
+            match collected.iter_mut().find(|(_, existing)| *existing == term) {
+                Some(entry) => entry.0 += coefficient,
+                None => collected.push((coefficient, term)),
+            }
+        }
+        
+        // c * sin(x)^2 * rest + c * cos(x)^2 * rest = c * rest
+        if opts.trig_identities {
+            for i in 0..collected.len() {
+                let Some(("sin", arg, rest)) = split_trig_square(&collected[i].1) else { continue };
+                let partner = (0..collected.len()).find(|&j| {
+                    collected[j].0 == collected[i].0
+                        && split_trig_square(&collected[j].1) == Some(("cos", arg.clone(), rest.clone()))
+                });
+                if let Some(j) = partner {
+                    collected[i].1 = rest;
+                    collected[j].0 = 0.0;
+                }
+            }
+        }
+        
+        let cancelled = collected.iter().any(|(coefficient, _)| *coefficient == 0.0);
+        if !cancelled && collected.len() == count_additive_terms(&self) {
+            return self;
+        }
+        
+        let scaled = |coefficient: f64, term: SymbolicExpr| match term {
+            SymbolicExpr::One => SymbolicExpr::Constant(coefficient),
+            term if coefficient == 1.0 => term,
+            term => SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(coefficient)), Box::new(term)),
+        };
+        
+        let mut result = SymbolicExpr::Zero;
+        for (coefficient, term) in collected {
+            if coefficient == 0.0 {
+                continue;
+            }
+            result = match (result, coefficient < 0.0) {
+                (SymbolicExpr::Zero, _) if term == SymbolicExpr::One => SymbolicExpr::Constant(coefficient),
+                (SymbolicExpr::Zero, true) => SymbolicExpr::Negate(Box::new(scaled(-coefficient, term))),
+                (acc, true) => SymbolicExpr::Subtract(Box::new(acc), Box::new(scaled(-coefficient, term))),
+                (SymbolicExpr::Zero, false) => scaled(coefficient, term),
+                (acc, false) => SymbolicExpr::Add(Box::new(acc), Box::new(scaled(coefficient, term))),
+            };
+        }
+        result
+    }
+    
+    /// The `order`-th derivative with respect to `var`, simplifying after each step.
+    /// Order zero returns the expression unchanged.
+    pub fn derivative_n(&self, var: &str, order: usize) -> Self {
+        (0..order).fold(self.clone(), |expr, _| expr.derivative(var).simplify())
+    }
+
+    /// Taylor polynomial of degree `order` in `var` around `about`,
+    /// `Σ f⁽ᵏ⁾(about) / k! (var - about)^k`, with other symbols kept as parameters.
+    /// Large-`r` (weak-field) expansions are taken by substituting `r = 1/u` and
+    /// expanding around `u = 0`. Fails if a numeric coefficient is not finite, e.g.
+    /// when `about` is a pole.
+    pub fn series(&self, var: &str, about: f64, order: usize) -> Result<SymbolicExpr, TensorError> {
+        let point = if about == 0.0 { SymbolicExpr::Zero } else { SymbolicExpr::Constant(about) };
+        let offset = if about == 0.0 {
+            SymbolicExpr::Variable(var.to_string())
+        } else {
+            SymbolicExpr::Subtract(Box::new(SymbolicExpr::Variable(var.to_string())), Box::new(point.clone()))
+        };
+        
+        let mut derivative = self.clone();
+        let mut factorial = 1.0;
+        let mut result = SymbolicExpr::Zero;
+        for k in 0..=order {
+            if k > 0 {
+                derivative = derivative.derivative(var).simplify();
+                factorial *= k as f64;
+            }
+            let value = derivative.substitute(var, &point).simplify();
+            let coefficient = if value.free_symbols().is_empty() {
+                let value = value.eval(&HashMap::new())?;
+                if !value.is_finite() {
+                    return Err(TensorError::ComputationError(format!(
+                        "Series of {} in {} around {} has a non-finite coefficient at order {}", self, var, about, k
+                    )));
+                }
+                SymbolicExpr::Constant(value / factorial)
+            } else {
+                SymbolicExpr::Divide(Box::new(value), Box::new(SymbolicExpr::Constant(factorial)))
+            };
+            
+            let power = match k {
+                0 => SymbolicExpr::One,
+                1 => offset.clone(),
+                _ => SymbolicExpr::Power(Box::new(offset.clone()), Box::new(SymbolicExpr::Constant(k as f64))),
+            };
+            result = SymbolicExpr::Add(
+                Box::new(result),
+                Box::new(SymbolicExpr::Multiply(Box::new(coefficient), Box::new(power))),
+            );
+        }
+        
+        Ok(result.simplify())
+    }
+
+    /// All first partial derivatives, `[∂f/∂v for v in vars]`.
+    pub fn gradient(&self, vars: &[String]) -> Vec<SymbolicExpr> {
+        vars.iter().map(|var| self.derivative(var).simplify()).collect()
+    }
+
+    /// Differentiates successively by each variable in `vars`, so `["x", "y"]`
+    /// gives `∂²f/∂y∂x`.
+    pub fn mixed_partial(&self, vars: &[String]) -> SymbolicExpr {
+        vars.iter().fold(self.simplify(), |expr, var| expr.derivative(var).simplify())
+    }
+
+    /// The derivative `f'(u)` of a one-argument function with respect to its argument.
+    /// Unknown functions differentiate to the primed name, so `a(t)` gives `a'(t)`.
+    fn function_derivative(name: &str, arg: &SymbolicExpr) -> Option<SymbolicExpr> {
+        let call = |f: &str| SymbolicExpr::Function(f.to_string(), vec![arg.clone()]);
+        let reciprocal = |expr: SymbolicExpr| SymbolicExpr::Divide(Box::new(SymbolicExpr::One), Box::new(expr));
+        let square = |expr: SymbolicExpr| SymbolicExpr::Power(Box::new(expr), Box::new(SymbolicExpr::Constant(2.0)));
+        
+        Some(match name {
+            "sin" => call("cos"),
+            "cos" => SymbolicExpr::Negate(Box::new(call("sin"))),
+            "tan" => reciprocal(square(call("cos"))),
+            "exp" => call("exp"),
+            "ln" | "log" => reciprocal(arg.clone()),
+            "sqrt" => reciprocal(SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(2.0)), Box::new(call("sqrt")))),
+            "cbrt" => reciprocal(SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(3.0)), Box::new(square(call("cbrt"))))),
+            "sinh" => call("cosh"),
+            "cosh" => call("sinh"),
+            "tanh" => reciprocal(square(call("cosh"))),
+            "abs" => SymbolicExpr::Divide(Box::new(arg.clone()), Box::new(call("abs"))),
+            _ if !BUILTIN_FUNCTIONS.contains(&name) => call(&format!("{}'", name)),
+            _ => return None,
+        })
+    }
+
+    /// Multiplies out products and integer powers of sums into a sum of monomials
+    /// `c * Π base^n`, then collects like terms. Factors are matched structurally, so
+    /// `x*y - y*x` expands to zero, and a monomial divided by itself cancels.
+    /// Quotients by sums are kept as a single factor `(a + b)^-1`.
+    pub fn expand(&self) -> Self {
+        let simplified = self.simplify();
+        match expand_terms(&simplified, usize::MAX, true) {
+            Some(terms) => rebuild_sum(terms),
+            None => simplified,
+        }
+    }
+
+    /// `simplify`, followed by `expand` when expanding stays within a fixed number of
+    /// terms and doesn't make the expression bigger. This is what the curvature
+    /// functions use, so that e.g. flat space in spherical coordinates produces no
+    /// spurious non-zero components. Expressions that only cancel over a common
+    /// denominator, like `1/(1 - 2*M/r) - r/(r - 2*M)`, also become zero.
+    pub fn simplify_expanded(&self) -> Self {
+        self.simplify_with(SimplifyOptions { expand: true, ..SimplifyOptions::default() })
+    }
+
+    /// The expansion step of `simplify_expanded`, applied to an already simplified
+    /// expression.
+    fn expanded(self, opts: &SimplifyOptions) -> Self {
+        const MAX_EXPANDED_TERMS: usize = 2000;
+        
+        let trig = opts.trig_identities;
+        match expand_terms(&self, MAX_EXPANDED_TERMS, trig) {
+            // Sums of fractions may only cancel over a common denominator
+            Some(terms) if !terms.is_empty() && rational_terms(&self, MAX_EXPANDED_TERMS, trig)
+                .is_some_and(|(numerator, _)| numerator.is_empty()) => SymbolicExpr::Zero,
+            Some(terms) => {
+                let expanded = rebuild_sum(terms);
+                if expanded.node_count() <= self.node_count() {
+                    expanded
+                } else {
+                    self
+                }
+            }
+            None => self,
+        }
+    }
+
+    /// Flattens a sum into `(coefficient, term)` pairs, with constants attached to
+    /// the term `One`.
+    fn additive_terms(&self, sign: f64, terms: &mut Vec<(f64, SymbolicExpr)>) {
+        match self {
+            SymbolicExpr::Add(a, b) => {
+                a.additive_terms(sign, terms);
+                b.additive_terms(sign, terms);
+            }
+            SymbolicExpr::Subtract(a, b) => {
+                a.additive_terms(sign, terms);
+                b.additive_terms(-sign, terms);
+            }
+            SymbolicExpr::Negate(a) => a.additive_terms(-sign, terms),
+            SymbolicExpr::Zero => {}
+            SymbolicExpr::One => terms.push((sign, SymbolicExpr::One)),
+            SymbolicExpr::Constant(c) => terms.push((sign * c, SymbolicExpr::One)),
+            SymbolicExpr::Multiply(a, b) => match (&**a, &**b) {
+                (SymbolicExpr::Constant(c), term) | (term, SymbolicExpr::Constant(c)) => {
+                    terms.push((sign * c, term.clone()))
+                }
+                _ => terms.push((sign, self.clone())),
+            },
+            _ => terms.push((sign, self.clone())),
+        }
+    }
+
     pub fn derivative(&self, var: &str) -> Self {
         match self {
             SymbolicExpr::Variable(v) => {
@@ -197,6 +589,7 @@ impl SymbolicExpr {
                     Box::new(right.derivative(var)),
                 )
             }
+            SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(Box::new(inner.derivative(var))),
             SymbolicExpr::Multiply(left, right) => {
                 // Product rule: (fg)' = f'g + fg'
                 SymbolicExpr::Add(
@@ -230,12 +623,12 @@ impl SymbolicExpr {
                 )
             }
             SymbolicExpr::Power(base, exp) => {
-                match (&**exp, &**base) {
+                match (exp.constant_value(), &**base) {
                     // Constant exponent: (f^n)' = n*f^(n-1)*f'
-                    (SymbolicExpr::Constant(n), _) => {
+                    (Some(n), _) => {
                         SymbolicExpr::Multiply(
                             Box::new(SymbolicExpr::Multiply(
-                                Box::new(SymbolicExpr::Constant(*n)),
+                                Box::new(SymbolicExpr::Constant(n)),
                                 Box::new(SymbolicExpr::Power(
                                     Box::new((**base).clone()),
                                     Box::new(SymbolicExpr::Constant(n - 1.0)),
@@ -244,78 +637,2739 @@ impl SymbolicExpr {
                             Box::new(base.derivative(var)),
                         )
                     }
-                    // For more complex cases, we'd need logarithmic differentiation
-                    _ => SymbolicExpr::Zero, // Simplified for now
-                }
-            }
-            SymbolicExpr::Function(name, args) => {
-                // Basic derivatives for common functions
-                match name.as_str() {
-                    "sin" if args.len() == 1 => {
-                        SymbolicExpr::Multiply(
-                            Box::new(SymbolicExpr::Function("cos".to_string(), args.clone())),
-                            Box::new(args[0].derivative(var)),
-                        )
-                    }
-                    "cos" if args.len() == 1 => {
+                    // Logarithmic differentiation: (f^g)' = f^g * (g' * ln(f) + g * f'/f)
+                    _ => {
                         SymbolicExpr::Multiply(
-                            Box::new(SymbolicExpr::Subtract(
-                                Box::new(SymbolicExpr::Zero),
-                                Box::new(SymbolicExpr::Function("sin".to_string(), args.clone())),
+                            Box::new(self.clone()),
+                            Box::new(SymbolicExpr::Add(
+                                Box::new(SymbolicExpr::Multiply(
+                                    Box::new(exp.derivative(var)),
+                                    Box::new(SymbolicExpr::Function("ln".to_string(), vec![(**base).clone()])),
+                                )),
+                                Box::new(SymbolicExpr::Divide(
+                                    Box::new(SymbolicExpr::Multiply(
+                                        Box::new((**exp).clone()),
+                                        Box::new(base.derivative(var)),
+                                    )),
+                                    Box::new((**base).clone()),
+                                )),
                             )),
-                            Box::new(args[0].derivative(var)),
                         )
                     }
-                    _ => SymbolicExpr::Zero, // Unknown function, assume constant for now
                 }
             }
+            SymbolicExpr::Function(name, args) if args.len() == 1 => {
+                // Chain rule: f(u)' = f'(u) * u'
+                match Self::function_derivative(name, &args[0]) {
+                    Some(outer) => SymbolicExpr::Multiply(Box::new(outer), Box::new(args[0].derivative(var))),
+                    None => SymbolicExpr::Zero,
+                }
+            }
+            // Unknown function of several arguments: df = Σ ∂_k f * du_k, with the partial
+            // derivatives named by `partial_derivative_name`
+            SymbolicExpr::Function(name, args) if !BUILTIN_FUNCTIONS.contains(&name.as_str()) => {
+                args.iter().enumerate().fold(SymbolicExpr::Zero, |acc, (k, arg)| {
+                    let partial = partial_derivative_name(name, args, k);
+                    SymbolicExpr::Add(
+                        Box::new(acc),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(SymbolicExpr::Function(partial, args.clone())),
+                            Box::new(arg.derivative(var)),
+                        )),
+                    )
+                })
+            }
+            SymbolicExpr::Function(..) => SymbolicExpr::Zero, // Not yet supported, assume constant for now
             SymbolicExpr::Zero => SymbolicExpr::Zero,
             SymbolicExpr::One => SymbolicExpr::Zero,
         }
     }
 
-    pub fn is_zero(&self) -> bool {
-        matches!(self, SymbolicExpr::Zero) || 
-        matches!(self, SymbolicExpr::Constant(val) if *val == 0.0)
+    /// Evaluates the expression numerically with the given variable bindings.
+    /// `pi` evaluates to π unless it is bound explicitly.
+    pub fn eval(&self, bindings: &HashMap<String, f64>) -> Result<f64, TensorError> {
+        match self {
+            SymbolicExpr::Variable(name) => match bindings.get(name) {
+                Some(val) => Ok(*val),
+                None if name == "pi" => Ok(std::f64::consts::PI),
+                None => Err(TensorError::ComputationError(format!("Unbound variable '{}'", name))),
+            },
+            SymbolicExpr::Constant(val) => Ok(*val),
+            SymbolicExpr::Add(left, right) => Ok(left.eval(bindings)? + right.eval(bindings)?),
+            SymbolicExpr::Subtract(left, right) => Ok(left.eval(bindings)? - right.eval(bindings)?),
+            SymbolicExpr::Negate(inner) => Ok(-inner.eval(bindings)?),
+            SymbolicExpr::Multiply(left, right) => Ok(left.eval(bindings)? * right.eval(bindings)?),
+            SymbolicExpr::Divide(left, right) => {
+                let denominator = right.eval(bindings)?;
+                if denominator == 0.0 {
+                    return Err(TensorError::ComputationError(format!("Division by zero in '{}'", self)));
+                }
+                Ok(left.eval(bindings)? / denominator)
+            }
+            SymbolicExpr::Power(base, exp) => {
+                let (base_val, exp_val) = (base.eval(bindings)?, exp.eval(bindings)?);
+                if base_val == 0.0 && exp_val < 0.0 {
+                    return Err(TensorError::ComputationError(format!("Division by zero in '{}'", self)));
+                }
+                Ok(base_val.powf(exp_val))
+            }
+            SymbolicExpr::Function(name, args) => {
+                if args.len() != 1 {
+                    return Err(TensorError::ComputationError(format!("Cannot evaluate function '{}'", self)));
+                }
+                let x = args[0].eval(bindings)?;
+                match name.as_str() {
+                    "sin" => Ok(x.sin()),
+                    "cos" => Ok(x.cos()),
+                    "tan" => Ok(x.tan()),
+                    "exp" => Ok(x.exp()),
+                    "ln" | "log" => Ok(x.ln()),
+                    "sqrt" => Ok(x.sqrt()),
+                    "cbrt" => Ok(x.cbrt()),
+                    "sinh" => Ok(x.sinh()),
+                    "cosh" => Ok(x.cosh()),
+                    "tanh" => Ok(x.tanh()),
+                    "abs" => Ok(x.abs()),
+                    _ => Err(TensorError::ComputationError(format!("Unknown function '{}'", name))),
+                }
+            }
+            SymbolicExpr::Zero => Ok(0.0),
+            SymbolicExpr::One => Ok(1.0),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Checks whether two expressions agree, first symbolically with `is_equivalent`
+    /// and then, if that is inconclusive, numerically at a few pseudo-random
+    /// bindings of their variables. Returns false if no sample point can be
+    /// evaluated for both sides.
+    pub fn approx_eq(&self, other: &SymbolicExpr) -> bool {
+        if self.is_equivalent(other) {
+            return true;
+        }
 
-    #[test]
-    fn test_parse_simple() {
-        assert_eq!(SymbolicExpr::parse("x").unwrap(), SymbolicExpr::Variable("x".to_string()));
-        assert_eq!(SymbolicExpr::parse("42").unwrap(), SymbolicExpr::Constant(42.0));
-        assert_eq!(SymbolicExpr::parse("0").unwrap(), SymbolicExpr::Zero);
-        assert_eq!(SymbolicExpr::parse("1").unwrap(), SymbolicExpr::One);
+        let mut variables = BTreeSet::new();
+        self.collect_variables(&mut variables);
+        other.collect_variables(&mut variables);
+
+        // Deterministic LCG so failures are reproducible
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next_value = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            0.5 + 2.0 * ((state >> 11) as f64 / (1u64 << 53) as f64)
+        };
+
+        let mut checked = 0;
+        for _ in 0..8 {
+            let bindings: HashMap<String, f64> = variables.iter()
+                .map(|var| (var.clone(), next_value()))
+                .collect();
+
+            if let (Ok(a), Ok(b)) = (self.eval(&bindings), other.eval(&bindings)) {
+                if !a.is_finite() || !b.is_finite() {
+                    continue;
+                }
+                if (a - b).abs() > 1e-9 * a.abs().max(b.abs()).max(1.0) {
+                    return false;
+                }
+                checked += 1;
+            }
+        }
+
+        checked > 0
     }
 
-    #[test]
-    fn test_parse_power() {
-        match SymbolicExpr::parse("r^2").unwrap() {
+    /// Names of the free parameters of the expression: every variable plus the
+    /// name of every function that `eval` doesn't know, such as `a` in `a(t)`.
+    /// The constant `pi` is not included.
+    pub fn free_symbols(&self) -> BTreeSet<String> {
+        let mut symbols = BTreeSet::new();
+        self.collect_free_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_free_symbols(&self, symbols: &mut BTreeSet<String>) {
+        match self {
+            SymbolicExpr::Variable(name) => {
+                if name != "pi" {
+                    symbols.insert(name.clone());
+                }
+            }
+            SymbolicExpr::Add(left, right)
+            | SymbolicExpr::Subtract(left, right)
+            | SymbolicExpr::Multiply(left, right)
+            | SymbolicExpr::Divide(left, right)
+            | SymbolicExpr::Power(left, right) => {
+                left.collect_free_symbols(symbols);
+                right.collect_free_symbols(symbols);
+            }
+            SymbolicExpr::Negate(inner) => inner.collect_free_symbols(symbols),
+            SymbolicExpr::Function(name, args) => {
+                if !BUILTIN_FUNCTIONS.contains(&name.as_str()) {
+                    symbols.insert(name.clone());
+                }
+                for arg in args {
+                    arg.collect_free_symbols(symbols);
+                }
+            }
+            SymbolicExpr::Constant(_) | SymbolicExpr::Zero | SymbolicExpr::One => {}
+        }
+    }
+
+    /// Names of all variables appearing in the expression.
+    pub(crate) fn variables(&self) -> BTreeSet<String> {
+        let mut variables = BTreeSet::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut BTreeSet<String>) {
+        match self {
+            SymbolicExpr::Variable(name) => {
+                variables.insert(name.clone());
+            }
+            SymbolicExpr::Add(left, right)
+            | SymbolicExpr::Subtract(left, right)
+            | SymbolicExpr::Multiply(left, right)
+            | SymbolicExpr::Divide(left, right)
+            | SymbolicExpr::Power(left, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            SymbolicExpr::Negate(inner) => inner.collect_variables(variables),
+            SymbolicExpr::Function(_, args) => {
+                for arg in args {
+                    arg.collect_variables(variables);
+                }
+            }
+            SymbolicExpr::Constant(_) | SymbolicExpr::Zero | SymbolicExpr::One => {}
+        }
+    }
+
+    /// Returns true if `var` occurs anywhere in the expression.
+    pub fn contains_variable(&self, var: &str) -> bool {
+        self.variables().contains(var)
+    }
+
+    /// Replaces every occurrence of the variable `var` with `replacement`, including
+    /// inside function arguments. The result is not simplified.
+    pub fn substitute(&self, var: &str, replacement: &SymbolicExpr) -> SymbolicExpr {
+        let sub = |expr: &SymbolicExpr| Box::new(expr.substitute(var, replacement));
+
+        match self {
+            SymbolicExpr::Variable(name) if name == var => replacement.clone(),
+            SymbolicExpr::Add(left, right) => SymbolicExpr::Add(sub(left), sub(right)),
+            SymbolicExpr::Subtract(left, right) => SymbolicExpr::Subtract(sub(left), sub(right)),
+            SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(sub(inner)),
+            SymbolicExpr::Multiply(left, right) => SymbolicExpr::Multiply(sub(left), sub(right)),
+            SymbolicExpr::Divide(left, right) => SymbolicExpr::Divide(sub(left), sub(right)),
+            SymbolicExpr::Power(base, exp) => SymbolicExpr::Power(sub(base), sub(exp)),
+            SymbolicExpr::Function(name, args) => SymbolicExpr::Function(
+                name.clone(),
+                args.iter().map(|arg| arg.substitute(var, replacement)).collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    /// Like `substitute`, but simplifies the result.
+    pub fn substitute_and_simplify(&self, var: &str, replacement: &SymbolicExpr) -> SymbolicExpr {
+        self.substitute(var, replacement).simplify()
+    }
+
+    /// Values of the given variables at which a denominator in the expression
+    /// vanishes, e.g. `r = 2*M` for `1/(1 - 2*M/r)`. Only denominators that are
+    /// rational in a variable are analysed, and only roots free of all `vars`
+    /// are reported.
+    ///
+    /// A restriction is removable when the expression as a whole is rational in
+    /// the variable and its reduced denominator does not vanish there, as for
+    /// `1/(1/(1 - 2*M/r))` at `r = 2*M`.
+    pub fn domain_restrictions(&self, vars: &[String]) -> Vec<DomainRestriction> {
+        let mut denominators = Vec::new();
+        self.collect_denominators(&mut denominators);
+
+        let mut restrictions: Vec<DomainRestriction> = Vec::new();
+        for denominator in denominators {
+            for var in vars.iter().filter(|var| denominator.contains_variable(var)) {
+                let Some((num, _)) = denominator.as_rational_function(var) else {
+                    continue;
+                };
+                for root in solve_polynomial(&num, var).unwrap_or_default() {
+                    let coordinate_free = vars.iter().all(|v| !root.contains_variable(v));
+                    let seen = restrictions.iter().any(|r| &r.variable == var && r.value.approx_eq(&root));
+                    if coordinate_free && !seen {
+                        let removable = self.is_finite_at(var, &root);
+                        restrictions.push(DomainRestriction { variable: var.clone(), value: root, removable });
+                    }
+                }
+            }
+        }
+
+        restrictions
+    }
+
+    /// Whether the expression, written as a rational function of `var`, stays
+    /// finite at `var = value`: the root is at least as repeated in the numerator
+    /// as in the denominator.
+    fn is_finite_at(&self, var: &str, value: &SymbolicExpr) -> bool {
+        match self.as_rational_function(var) {
+            Some((num, den)) => root_multiplicity(&num, value) >= root_multiplicity(&den, value),
+            None => false,
+        }
+    }
+
+    fn collect_denominators<'a>(&'a self, denominators: &mut Vec<&'a SymbolicExpr>) {
+        match self {
+            SymbolicExpr::Divide(left, right) => {
+                denominators.push(right);
+                left.collect_denominators(denominators);
+                right.collect_denominators(denominators);
+            }
             SymbolicExpr::Power(base, exp) => {
-                assert_eq!(*base, SymbolicExpr::Variable("r".to_string()));
-                assert_eq!(*exp, SymbolicExpr::Constant(2.0));
+                if exp.constant_value().is_some_and(|n| n < 0.0) {
+                    denominators.push(base);
+                }
+                base.collect_denominators(denominators);
+                exp.collect_denominators(denominators);
             }
-            _ => panic!("Expected power expression"),
+            SymbolicExpr::Add(left, right)
+            | SymbolicExpr::Subtract(left, right)
+            | SymbolicExpr::Multiply(left, right) => {
+                left.collect_denominators(denominators);
+                right.collect_denominators(denominators);
+            }
+            SymbolicExpr::Negate(inner) => inner.collect_denominators(denominators),
+            SymbolicExpr::Function(_, args) => {
+                for arg in args {
+                    arg.collect_denominators(denominators);
+                }
+            }
+            _ => {}
         }
     }
 
-    #[test]
-    fn test_simplify() {
-        let expr = SymbolicExpr::Add(
-            Box::new(SymbolicExpr::Constant(2.0)),
-            Box::new(SymbolicExpr::Constant(3.0)),
-        );
-        assert_eq!(expr.simplify(), SymbolicExpr::Constant(5.0));
+    /// Writes the expression as a ratio of polynomials in `var`, returning the
+    /// numerator and denominator coefficients (lowest power first). Returns
+    /// `None` if `var` appears somewhere other than in integer powers, e.g.
+    /// inside a function call.
+    pub fn as_rational_function(&self, var: &str) -> Option<(Vec<SymbolicExpr>, Vec<SymbolicExpr>)> {
+        if !self.contains_variable(var) {
+            return Some((vec![self.clone()], vec![SymbolicExpr::One]));
+        }
+
+        let (num, den) = match self {
+            SymbolicExpr::Variable(_) => (vec![SymbolicExpr::Zero, SymbolicExpr::One], vec![SymbolicExpr::One]),
+            SymbolicExpr::Add(left, right) | SymbolicExpr::Subtract(left, right) => {
+                let (n1, d1) = left.as_rational_function(var)?;
+                let (n2, d2) = right.as_rational_function(var)?;
+                let combine = |a: &[SymbolicExpr], b: &[SymbolicExpr]| match self {
+                    SymbolicExpr::Add(_, _) => poly_add(a, b),
+                    _ => poly_sub(a, b),
+                };
+                if d1 == d2 {
+                    (combine(&n1, &n2), d1)
+                } else {
+                    (combine(&poly_mul(&n1, &d2), &poly_mul(&n2, &d1)), poly_mul(&d1, &d2))
+                }
+            }
+            SymbolicExpr::Negate(inner) => {
+                let (n, d) = inner.as_rational_function(var)?;
+                (poly_sub(&[], &n), d)
+            }
+            SymbolicExpr::Multiply(left, right) => {
+                let (n1, d1) = left.as_rational_function(var)?;
+                let (n2, d2) = right.as_rational_function(var)?;
+                (poly_mul(&n1, &n2), poly_mul(&d1, &d2))
+            }
+            SymbolicExpr::Divide(left, right) => {
+                let (n1, d1) = left.as_rational_function(var)?;
+                let (n2, d2) = right.as_rational_function(var)?;
+                (poly_mul(&n1, &d2), poly_mul(&d1, &n2))
+            }
+            SymbolicExpr::Power(base, exp) => {
+                let k = exp.constant_value()?;
+                if k.fract() != 0.0 {
+                    return None;
+                }
+                let (n, d) = base.as_rational_function(var)?;
+                let (n, d) = if k < 0.0 { (d, n) } else { (n, d) };
+                let mut num = vec![SymbolicExpr::One];
+                let mut den = vec![SymbolicExpr::One];
+                for _ in 0..(k.abs() as usize) {
+                    num = poly_mul(&num, &n);
+                    den = poly_mul(&den, &d);
+                }
+                (num, den)
+            }
+            _ => return None,
+        };
+
+        let mut num = poly_trim(num);
+        let mut den = poly_trim(den);
+
+        // Cancel common powers of var
+        while num.len() > 1 && den.len() > 1 && num[0].is_zero() && den[0].is_zero() {
+            num.remove(0);
+            den.remove(0);
+        }
+
+        Some((num, den))
     }
 
-    #[test]
-    fn test_derivative() {
-        let expr = SymbolicExpr::Variable("x".to_string());
-        assert_eq!(expr.derivative("x"), SymbolicExpr::One);
-        assert_eq!(expr.derivative("y"), SymbolicExpr::Zero);
+    /// Splits a constant or a product with a constant factor, `c * x` or `x * c`,
+    /// into `(c, x)`.
+    fn coefficient(&self) -> Option<(f64, Option<SymbolicExpr>)> {
+        if let Some(c) = self.constant_value() {
+            return Some((c, None));
+        }
+        match self {
+            SymbolicExpr::Multiply(a, b) => match (a.constant_value(), b.constant_value()) {
+                (Some(c), _) => Some((c, Some((**b).clone()))),
+                (_, Some(c)) => Some((c, Some((**a).clone()))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The numeric value of a `Zero`, `One` or `Constant` node.
+    fn constant_value(&self) -> Option<f64> {
+        match self {
+            SymbolicExpr::Constant(val) => Some(*val),
+            SymbolicExpr::Zero => Some(0.0),
+            SymbolicExpr::One => Some(1.0),
+            _ => None,
+        }
+    }
+
+    /// Number of nodes in the expression tree.
+    pub fn node_count(&self) -> usize {
+        match self {
+            SymbolicExpr::Add(left, right)
+            | SymbolicExpr::Subtract(left, right)
+            | SymbolicExpr::Multiply(left, right)
+            | SymbolicExpr::Divide(left, right)
+            | SymbolicExpr::Power(left, right) => 1 + left.node_count() + right.node_count(),
+            SymbolicExpr::Negate(inner) => 1 + inner.node_count(),
+            SymbolicExpr::Function(_, args) => 1 + args.iter().map(|arg| arg.node_count()).sum::<usize>(),
+            _ => 1,
+        }
+    }
+
+    /// Renders the expression in syntax SymPy's `sympify` accepts: `**` for powers,
+    /// explicit `*` for products and only the parentheses precedence requires, e.g.
+    /// `1/(1 - 2*M/r)`.
+    pub fn to_sympy(&self) -> String {
+        self.sympy_with_precedence().0
+    }
+
+    /// SymPy text for the expression together with its binding strength: 1 for sums
+    /// and negations, 2 for products and quotients, 3 for powers and 4 for atoms.
+    fn sympy_with_precedence(&self) -> (String, u8) {
+        let wrap = |expr: &SymbolicExpr, min: u8| {
+            let (text, precedence) = expr.sympy_with_precedence();
+            if precedence < min { format!("({})", text) } else { text }
+        };
+        match self {
+            SymbolicExpr::Variable(var) => (var.clone(), 4),
+            SymbolicExpr::Constant(val) if *val < 0.0 => (self.to_string(), 1),
+            SymbolicExpr::Constant(_) | SymbolicExpr::Zero | SymbolicExpr::One => (self.to_string(), 4),
+            SymbolicExpr::Add(left, right) => (format!("{} + {}", wrap(left, 1), wrap(right, 2)), 1),
+            SymbolicExpr::Subtract(left, right) if left.is_zero() => (format!("-{}", wrap(right, 2)), 1),
+            SymbolicExpr::Subtract(left, right) => (format!("{} - {}", wrap(left, 1), wrap(right, 2)), 1),
+            SymbolicExpr::Negate(inner) => (format!("-{}", wrap(inner, 2)), 1),
+            // A leading minus binds like a factor, so -a*b needs no parentheses
+            SymbolicExpr::Multiply(left, right) => (format!("{}*{}", wrap(left, left.negation_precedence()), wrap(right, 2)), 2),
+            SymbolicExpr::Divide(left, right) => (format!("{}/{}", wrap(left, left.negation_precedence()), wrap(right, 3)), 2),
+            // ** is right-associative, so a power base always needs parentheses
+            SymbolicExpr::Power(base, exp) => (format!("{}**{}", wrap(base, 4), wrap(exp, 4)), 3),
+            SymbolicExpr::Function(name, args) => {
+                let name = match name.as_str() {
+                    "ln" => "log",
+                    "abs" => "Abs",
+                    other => other,
+                };
+                let args = args.iter().map(|arg| arg.to_sympy()).collect::<Vec<_>>().join(", ");
+                (format!("{}({})", name, args), 4)
+            }
+        }
+    }
+
+    /// Minimum precedence a left operand of a product needs to go unparenthesized:
+    /// a negation may lead a product as is, anything else must bind like a factor.
+    fn negation_precedence(&self) -> u8 {
+        match self {
+            SymbolicExpr::Subtract(zero, _) if zero.is_zero() => 1,
+            SymbolicExpr::Negate(_) => 1,
+            SymbolicExpr::Constant(val) if *val < 0.0 => 1,
+            _ => 2,
+        }
+    }
+
+    /// Renders the expression as LaTeX, using `\frac` for division, braced exponents,
+    /// `\sin`-style function names and Greek letters for variables like `theta` or `Omega_m`.
+    pub fn to_latex(&self) -> String {
+        self.latex_with_precedence().0
+    }
+
+    /// The `Display` form with Greek letter names of variables and functions shown as
+    /// symbols, e.g. `(r^2 * sin(θ)^2)` or `Ω_m`.
+    pub fn to_unicode(&self) -> String {
+        self.rename_symbols(&unicode_symbol).to_string()
+    }
+
+    /// Renames every variable and non-builtin function.
+    fn rename_symbols(&self, rename: &dyn Fn(&str) -> String) -> SymbolicExpr {
+        let sub = |expr: &SymbolicExpr| Box::new(expr.rename_symbols(rename));
+
+        match self {
+            SymbolicExpr::Variable(name) => SymbolicExpr::Variable(rename(name)),
+            SymbolicExpr::Add(left, right) => SymbolicExpr::Add(sub(left), sub(right)),
+            SymbolicExpr::Subtract(left, right) => SymbolicExpr::Subtract(sub(left), sub(right)),
+            SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(sub(inner)),
+            SymbolicExpr::Multiply(left, right) => SymbolicExpr::Multiply(sub(left), sub(right)),
+            SymbolicExpr::Divide(left, right) => SymbolicExpr::Divide(sub(left), sub(right)),
+            SymbolicExpr::Power(base, exp) => SymbolicExpr::Power(sub(base), sub(exp)),
+            SymbolicExpr::Function(name, args) => SymbolicExpr::Function(
+                if BUILTIN_FUNCTIONS.contains(&name.as_str()) { name.clone() } else { rename(name) },
+                args.iter().map(|arg| arg.rename_symbols(rename)).collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    /// LaTeX for the expression together with its binding strength: 1 for sums and
+    /// negations, 2 for products, 3 for powers and 4 for atoms.
+    fn latex_with_precedence(&self) -> (String, u8) {
+        let wrap = |expr: &SymbolicExpr, min: u8| {
+            let (latex, precedence) = expr.latex_with_precedence();
+            if precedence < min { format!("\\left({}\\right)", latex) } else { latex }
+        };
+        match self {
+            SymbolicExpr::Variable(var) => (latex_symbol(var), 4),
+            SymbolicExpr::Constant(val) if *val < 0.0 => (format!("-{}", SymbolicExpr::Constant(-val)), 1),
+            SymbolicExpr::Constant(_) | SymbolicExpr::Zero | SymbolicExpr::One => (self.to_string(), 4),
+            SymbolicExpr::Add(left, right) => (format!("{} + {}", wrap(left, 1), wrap(right, 2)), 1),
+            SymbolicExpr::Subtract(left, right) if left.is_zero() => (format!("-{}", wrap(right, 2)), 1),
+            SymbolicExpr::Subtract(left, right) => (format!("{} - {}", wrap(left, 1), wrap(right, 2)), 1),
+            SymbolicExpr::Negate(inner) => (format!("-{}", wrap(inner, 2)), 1),
+            SymbolicExpr::Multiply(left, right) => {
+                let separator = if right.constant_value().is_some() { " \\cdot " } else { " " };
+                let left = wrap(left, left.negation_precedence());
+                (format!("{}{}{}", left, separator, wrap(right, 3)), 2)
+            }
+            SymbolicExpr::Divide(left, right) => (format!("\\frac{{{}}}{{{}}}", left.to_latex(), right.to_latex()), 4),
+            SymbolicExpr::Power(base, exp) => match &**base {
+                // sin(θ)^2 is written \sin^{2}\left(\theta\right)
+                SymbolicExpr::Function(name, args) if args.len() == 1 && latex_function_name(name).starts_with("\\") && name != "sqrt" => {
+                    (format!("{}^{{{}}}\\left({}\\right)", latex_function_name(name), exp.to_latex(), args[0].to_latex()), 3)
+                }
+                _ => (format!("{}^{{{}}}", wrap(base, 4), exp.to_latex()), 3),
+            },
+            SymbolicExpr::Function(name, args) if name == "sqrt" && args.len() == 1 => {
+                (format!("\\sqrt{{{}}}", args[0].to_latex()), 4)
+            }
+            SymbolicExpr::Function(name, args) => {
+                let args = args.iter().map(|arg| arg.to_latex()).collect::<Vec<_>>().join(", ");
+                (format!("{}\\left({}\\right)", latex_function_name(name), args), 4)
+            }
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        matches!(self, SymbolicExpr::Zero) || 
+        matches!(self, SymbolicExpr::Constant(val) if *val == 0.0)
+    }
+
+    /// Checks whether two expressions are mathematically equal, i.e. they have the
+    /// same normal form: written over a common denominator, with numerator and
+    /// denominator multiplied out, like terms collected and `sin^2 + cos^2` reduced.
+    /// Expressions too large to multiply out are compared after `simplify`, up to the
+    /// order and grouping of sums and products.
+    pub fn is_equivalent(&self, other: &SymbolicExpr) -> bool {
+        self.normal_form() == other.normal_form()
+    }
+
+    /// The form compared by `is_equivalent` and hashed by `structural_hash`.
+    fn normal_form(&self) -> SymbolicExpr {
+        const MAX_NORMAL_FORM_TERMS: usize = 2000;
+        
+        let simplified = self.simplify();
+        let Some((numerator, denominator)) = rational_terms(&simplified, MAX_NORMAL_FORM_TERMS, true) else {
+            return simplified.canonicalize();
+        };
+        if numerator.is_empty() {
+            return SymbolicExpr::Zero;
+        }
+        // Clear negative powers, which `rational_terms` leaves in place of
+        // single-monomial denominators
+        let mut clearing = Monomial::constant(1.0);
+        for term in numerator.iter().chain(&denominator) {
+            for (key, base, exponent) in &term.factors {
+                let current = clearing.factors.iter().find(|(k, _, _)| k == key).map_or(0.0, |(_, _, e)| *e);
+                if -exponent > current {
+                    clearing = clearing.times(&Monomial::factor(base.clone(), -exponent - current));
+                }
+            }
+        }
+        let numerator = multiply_terms(&numerator, std::slice::from_ref(&clearing), usize::MAX, true).unwrap_or_default();
+        let denominator = multiply_terms(&denominator, &[clearing], usize::MAX, true).unwrap_or_default();
+        // Scale the quotient so that the denominator's leading coefficient is 1
+        let scale = denominator[0].coefficient;
+        let scaled = |terms: Vec<Monomial>| rebuild_sum(terms.into_iter().map(|mut term| {
+            term.coefficient /= scale;
+            term
+        }).collect());
+        let quotient = match denominator.as_slice() {
+            [term] if term.factors.is_empty() => scaled(numerator),
+            _ => SymbolicExpr::Divide(Box::new(scaled(numerator)), Box::new(scaled(denominator))),
+        };
+        quotient.canonicalize()
+    }
+
+    /// Deterministic canonical form: nested sums and products are flattened, their
+    /// constants folded into a leading term or coefficient, and the remaining operands
+    /// sorted, so that `a + b` and `b + a` become the same tree. Unlike `simplify`,
+    /// no algebraic rewriting is done. Canonicalizing twice gives the same result,
+    /// which makes the canonical form usable as a cache or deduplication key.
+    pub fn canonicalize(&self) -> SymbolicExpr {
+        match self {
+            SymbolicExpr::Add(..) | SymbolicExpr::Subtract(..) | SymbolicExpr::Negate(..) => {
+                let mut terms = Vec::new();
+                self.collect_canonical_terms(false, &mut terms);
+
+                let mut constant = 0.0;
+                let mut rest = Vec::new();
+                for term in terms {
+                    match term.constant_value() {
+                        Some(val) => constant += val,
+                        None => rest.push(term),
+                    }
+                }
+                rest.sort_by_cached_key(|term| term.to_string());
+                if constant != 0.0 || rest.is_empty() {
+                    rest.insert(0, SymbolicExpr::canonical_constant(constant));
+                }
+                rest.into_iter()
+                    .reduce(|sum, term| SymbolicExpr::Add(Box::new(sum), Box::new(term)))
+                    .unwrap_or(SymbolicExpr::Zero)
+            }
+            SymbolicExpr::Multiply(..) => {
+                let mut factors = Vec::new();
+                self.collect_canonical_factors(&mut factors);
+
+                let mut coefficient = 1.0;
+                let mut rest = Vec::new();
+                for factor in factors {
+                    match factor.constant_value() {
+                        Some(val) => coefficient *= val,
+                        None => rest.push(factor),
+                    }
+                }
+                if coefficient == 0.0 {
+                    return SymbolicExpr::Zero;
+                }
+                rest.sort_by_cached_key(|factor| factor.to_string());
+                if coefficient != 1.0 || rest.is_empty() {
+                    rest.insert(0, SymbolicExpr::canonical_constant(coefficient));
+                }
+                rest.into_iter()
+                    .reduce(|product, factor| SymbolicExpr::Multiply(Box::new(product), Box::new(factor)))
+                    .unwrap_or(SymbolicExpr::One)
+            }
+            SymbolicExpr::Divide(left, right) => {
+                SymbolicExpr::Divide(Box::new(left.canonicalize()), Box::new(right.canonicalize()))
+            }
+            SymbolicExpr::Power(base, exp) => {
+                SymbolicExpr::Power(Box::new(base.canonicalize()), Box::new(exp.canonicalize()))
+            }
+            SymbolicExpr::Function(name, args) => {
+                SymbolicExpr::Function(name.clone(), args.iter().map(|arg| arg.canonicalize()).collect())
+            }
+            SymbolicExpr::Constant(val) => SymbolicExpr::canonical_constant(*val),
+            _ => self.clone(),
+        }
+    }
+
+    /// Extracts every non-trivial sub-expression that occurs more than once into a
+    /// named temporary `_cse<k>`, returning the rewritten expression and the
+    /// definitions in dependency order (each may refer to earlier names).
+    pub fn common_subexpressions(&self) -> (SymbolicExpr, Vec<(String, SymbolicExpr)>) {
+        let mut cse = CseState::new(std::slice::from_ref(self));
+        let rewritten = cse.rewrite(self);
+        (rewritten, cse.bindings)
+    }
+
+    /// Hash of the normal form, for keying memoization tables. Expressions that
+    /// `is_equivalent` matches always hash equal.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.normal_form().hash_tree(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_tree<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            SymbolicExpr::Variable(var) => var.hash(state),
+            // +0.0 and -0.0 compare equal, so they must hash equal
+            SymbolicExpr::Constant(val) => (val + 0.0).to_bits().hash(state),
+            SymbolicExpr::Add(left, right)
+            | SymbolicExpr::Subtract(left, right)
+            | SymbolicExpr::Multiply(left, right)
+            | SymbolicExpr::Divide(left, right)
+            | SymbolicExpr::Power(left, right) => {
+                left.hash_tree(state);
+                right.hash_tree(state);
+            }
+            SymbolicExpr::Negate(inner) => inner.hash_tree(state),
+            SymbolicExpr::Function(name, args) => {
+                name.hash(state);
+                args.len().hash(state);
+                for arg in args {
+                    arg.hash_tree(state);
+                }
+            }
+            SymbolicExpr::Zero | SymbolicExpr::One => {}
+        }
+    }
+
+    /// Canonical terms of a sum, with subtracted terms negated.
+    fn collect_canonical_terms(&self, negate: bool, terms: &mut Vec<SymbolicExpr>) {
+        match self {
+            SymbolicExpr::Add(left, right) => {
+                left.collect_canonical_terms(negate, terms);
+                right.collect_canonical_terms(negate, terms);
+            }
+            SymbolicExpr::Subtract(left, right) => {
+                left.collect_canonical_terms(negate, terms);
+                right.collect_canonical_terms(!negate, terms);
+            }
+            SymbolicExpr::Negate(inner) => inner.collect_canonical_terms(!negate, terms),
+            _ if negate => terms.push(
+                SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(-1.0)), Box::new(self.clone())).canonicalize()
+            ),
+            _ => terms.push(self.canonicalize()),
+        }
+    }
+
+    /// Canonical factors of a product.
+    fn collect_canonical_factors(&self, factors: &mut Vec<SymbolicExpr>) {
+        match self {
+            SymbolicExpr::Multiply(left, right) => {
+                left.collect_canonical_factors(factors);
+                right.collect_canonical_factors(factors);
+            }
+            _ => factors.push(self.canonicalize()),
+        }
+    }
+
+    fn canonical_constant(val: f64) -> SymbolicExpr {
+        if val == 0.0 {
+            SymbolicExpr::Zero
+        } else if val == 1.0 {
+            SymbolicExpr::One
+        } else {
+            SymbolicExpr::Constant(val)
+        }
+    }
+}
+
+/// Memoized derivatives. Entries are keyed on the canonical form of the simplified
+/// expression, so repeated subexpressions (such as the two halves of a symmetric
+/// metric) and rearrangements of one, like `a*b` and `b*a`, are only differentiated
+/// once. The key of each expression is itself remembered as written, so that
+/// repeating one exactly does not simplify it again.
+#[derive(Debug, Clone, Default)]
+pub struct DerivativeCache {
+    keys: HashMap<u64, Vec<(SymbolicExpr, SymbolicExpr)>>,
+    entries: HashMap<(u64, String), Vec<(SymbolicExpr, SymbolicExpr)>>,
+    len: usize,
+}
+
+impl DerivativeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `expr.derivative(var)`, computed at most once per distinct expression.
+    pub fn derivative(&mut self, expr: &SymbolicExpr, var: &str) -> SymbolicExpr {
+        // Constants and zeros are common in metrics and cheaper to differentiate than hash
+        if expr.constant_value().is_some() {
+            return SymbolicExpr::Zero;
+        }
+        let key = self.key(expr);
+        let bucket = self.entries.entry((tree_hash(&key), var.to_string())).or_default();
+        if let Some((_, derivative)) = bucket.iter().find(|(cached, _)| *cached == key) {
+            return derivative.clone();
+        }
+        let derivative = expr.derivative(var);
+        bucket.push((key, derivative.clone()));
+        self.len += 1;
+        derivative
+    }
+
+    /// Canonical form of the simplified `expr`, computed once per expression as written.
+    fn key(&mut self, expr: &SymbolicExpr) -> SymbolicExpr {
+        let bucket = self.keys.entry(tree_hash(expr)).or_default();
+        if let Some((_, key)) = bucket.iter().find(|(written, _)| written == expr) {
+            return key.clone();
+        }
+        let key = expr.simplify().canonicalize();
+        bucket.push((expr.clone(), key.clone()));
+        key
+    }
+
+    /// Derivatives of `expr` with respect to each of `vars`.
+    pub fn gradient(&mut self, expr: &SymbolicExpr, vars: &[String]) -> Vec<SymbolicExpr> {
+        vars.iter().map(|var| self.derivative(expr, var)).collect()
+    }
+
+    /// Number of distinct derivatives computed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+fn tree_hash(expr: &SymbolicExpr) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    expr.hash_tree(&mut hasher);
+    hasher.finish()
+}
+
+/// A value of `variable` at which a denominator in an expression vanishes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DomainRestriction {
+    pub variable: String,
+    pub value: SymbolicExpr,
+    /// The expression has a finite limit here; only its written form is undefined.
+    pub removable: bool,
+}
+
+/// Concrete values for the variables and unknown functions of an expression, e.g.
+/// `a(t) = exp(H*t)` for the FLRW scale factor.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolicContext {
+    variables: HashMap<String, SymbolicExpr>,
+    functions: HashMap<String, (String, SymbolicExpr)>,
+}
+
+impl SymbolicContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a variable. Variables and functions already defined are substituted
+    /// into `value` immediately, so later definitions may build on earlier ones.
+    pub fn set_variable(&mut self, name: &str, value: SymbolicExpr) {
+        let value = self.substitute(&value);
+        self.variables.insert(name.to_string(), value);
+    }
+
+    /// Defines `name(parameter) = body`. The derivatives `name'` and `name''` that
+    /// `derivative` produces for unknown functions are defined alongside it, so they
+    /// stay consistent with the body. Defining `name'` afterwards overrides the
+    /// computed derivative, for bodies `derivative` cannot handle.
+    pub fn define_function(&mut self, name: &str, parameter: &str, body: SymbolicExpr) {
+        let first = body.derivative(parameter).simplify();
+        let second = first.derivative(parameter).simplify();
+
+        self.functions.insert(format!("{}''", name), (parameter.to_string(), second));
+        self.functions.insert(format!("{}'", name), (parameter.to_string(), first));
+        self.functions.insert(name.to_string(), (parameter.to_string(), body));
+    }
+
+    /// Substitutes every defined variable and function into `expr` and simplifies.
+    pub fn apply(&self, expr: &SymbolicExpr) -> SymbolicExpr {
+        self.substitute(expr).simplify()
+    }
+
+    /// Like `apply`, but leaves the result unsimplified.
+    pub fn substitute(&self, expr: &SymbolicExpr) -> SymbolicExpr {
+        let sub = |e: &SymbolicExpr| Box::new(self.substitute(e));
+
+        match expr {
+            SymbolicExpr::Variable(name) => self.variables.get(name).cloned().unwrap_or_else(|| expr.clone()),
+            SymbolicExpr::Add(left, right) => SymbolicExpr::Add(sub(left), sub(right)),
+            SymbolicExpr::Subtract(left, right) => SymbolicExpr::Subtract(sub(left), sub(right)),
+            SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(sub(inner)),
+            SymbolicExpr::Multiply(left, right) => SymbolicExpr::Multiply(sub(left), sub(right)),
+            SymbolicExpr::Divide(left, right) => SymbolicExpr::Divide(sub(left), sub(right)),
+            SymbolicExpr::Power(base, exp) => SymbolicExpr::Power(sub(base), sub(exp)),
+            SymbolicExpr::Function(name, args) => {
+                let args: Vec<SymbolicExpr> = args.iter().map(|arg| self.substitute(arg)).collect();
+                match (self.functions.get(name), args.as_slice()) {
+                    (Some((parameter, body)), [arg]) => {
+                        // The body sees the context's variables, with the parameter bound
+                        // to the argument
+                        let mut bound = self.clone();
+                        bound.variables.insert(parameter.clone(), arg.clone());
+                        bound.substitute(body)
+                    }
+                    _ => SymbolicExpr::Function(name.clone(), args),
+                }
+            }
+            _ => expr.clone(),
+        }
+    }
+}
+
+/// Name of the partial derivative of the unknown function `name(args)` with respect
+/// to argument `k`. Partials are primed and indexed by the argument: `f'_r` for a
+/// variable argument `r`, and the position, `f'_2`, for any other. Repeated partials
+/// list their indices in argument order, so `phi'_t_r` is both ∂_t∂_r φ and ∂_r∂_t φ.
+fn partial_derivative_name(name: &str, args: &[SymbolicExpr], k: usize) -> String {
+    let labels: Vec<String> = args.iter().enumerate()
+        .map(|(i, arg)| match arg {
+            SymbolicExpr::Variable(v) => v.clone(),
+            _ => (i + 1).to_string(),
+        })
+        .collect();
+    
+    // Split an existing `base'_i_j` name into its base and argument positions
+    let parse_indices = |mut rest: &str| {
+        let mut positions = Vec::new();
+        while !rest.is_empty() {
+            let (position, label) = labels.iter().enumerate()
+                .filter(|(_, label)| rest == label.as_str() || rest.starts_with(&format!("{}_", label)))
+                .max_by_key(|(_, label)| label.len())?;
+            positions.push(position);
+            rest = rest[label.len()..].trim_start_matches('_');
+        }
+        Some(positions)
+    };
+    let (base, mut positions) = name.split_once("'_")
+        .and_then(|(base, suffix)| Some((base, parse_indices(suffix)?)))
+        .unwrap_or((name, Vec::new()));
+    
+    positions.push(k);
+    positions.sort();
+    let indices: Vec<&str> = positions.iter().map(|&i| labels[i].as_str()).collect();
+    format!("{}'_{}", base, indices.join("_"))
+}
+
+/// Functions that `eval` knows how to evaluate.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "exp", "ln", "log", "sqrt", "cbrt", "sinh", "cosh", "tanh", "abs",
+];
+
+/// Greek letter names and their Unicode symbols, shared by `to_latex`, which writes
+/// them as commands like `\theta`, and `to_unicode`. The uppercase letters that look
+/// Latin have no LaTeX command and are left out.
+const GREEK_MAP: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"),
+    ("sigma", "σ"), ("tau", "τ"), ("upsilon", "υ"), ("phi", "φ"), ("chi", "χ"), ("psi", "ψ"),
+    ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"), ("Theta", "Θ"), ("Lambda", "Λ"),
+    ("Xi", "Ξ"), ("Pi", "Π"), ("Sigma", "Σ"), ("Upsilon", "Υ"), ("Phi", "Φ"), ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+];
+
+fn greek_letter(name: &str) -> Option<&'static str> {
+    GREEK_MAP.iter().find(|(letter, _)| *letter == name).map(|(_, symbol)| *symbol)
+}
+
+/// Unicode for a variable or function name, replacing every Greek letter name between
+/// underscores and keeping any primes: `Omega_m` becomes `Ω_m` and `phi'_r` `φ'_r`.
+fn unicode_symbol(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let base = part.trim_end_matches('\'');
+            format!("{}{}", greek_letter(base).unwrap_or(base), &part[base.len()..])
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// LaTeX for a variable name, turning Greek letter names into commands and the part
+/// after the first underscore into a subscript: `Omega_m` becomes `\Omega_m`.
+fn latex_symbol(name: &str) -> String {
+    let greek = |part: &str| {
+        if greek_letter(part).is_some() { format!("\\{}", part) } else { part.to_string() }
+    };
+    match name.split_once('_') {
+        Some((base, subscript)) if subscript.chars().count() == 1 => format!("{}_{}", greek(base), greek(subscript)),
+        Some((base, subscript)) => format!("{}_{{{}}}", greek(base), greek(subscript)),
+        None => greek(name),
+    }
+}
+
+/// LaTeX for a function name: builtins become commands like `\sin`, single letters
+/// (with any primes) are kept, and longer names are set upright.
+fn latex_function_name(name: &str) -> String {
+    if BUILTIN_FUNCTIONS.contains(&name) && !matches!(name, "abs" | "cbrt") {
+        return format!("\\{}", name);
+    }
+    let base = name.trim_end_matches('\'');
+    if base.chars().count() <= 1 || greek_letter(base).is_some() {
+        format!("{}{}", latex_symbol(base), &name[base.len()..])
+    } else {
+        format!("\\operatorname{{{}}}{}", base, &name[base.len()..])
+    }
+}
+
+/// Finds the roots of the polynomial `Σ coeffs[k] * var^k` (lowest power first).
+/// Degrees up to two are solved symbolically with the quadratic formula, after
+/// factoring out any power of `var`. Higher degrees are solved numerically when
+/// every coefficient is a number, returning only the real roots in ascending
+/// order. Repeated roots are returned once.
+pub fn solve_polynomial(coeffs: &[SymbolicExpr], var: &str) -> Result<Vec<SymbolicExpr>, TensorError> {
+    let mut coeffs = poly_trim(coeffs.to_vec());
+    let mut roots = Vec::new();
+
+    // A zero constant term means var = 0 is a root
+    if coeffs.len() > 1 && coeffs[0].is_zero() {
+        roots.push(SymbolicExpr::Zero);
+        while coeffs.len() > 1 && coeffs[0].is_zero() {
+            coeffs.remove(0);
+        }
+    }
+
+    let neg = |e: SymbolicExpr| SymbolicExpr::Negate(Box::new(e));
+    let mul = |a: SymbolicExpr, b: SymbolicExpr| SymbolicExpr::Multiply(Box::new(a), Box::new(b));
+    let div = |a: SymbolicExpr, b: SymbolicExpr| SymbolicExpr::Divide(Box::new(a), Box::new(b));
+
+    match coeffs.len() {
+        0 | 1 => {}
+        2 => roots.push(div(neg(coeffs[0].clone()), coeffs[1].clone()).simplify()),
+        3 => {
+            let (c, b, a) = (coeffs[0].clone(), coeffs[1].clone(), coeffs[2].clone());
+            let discriminant = SymbolicExpr::Subtract(
+                Box::new(SymbolicExpr::Power(Box::new(b.clone()), Box::new(SymbolicExpr::Constant(2.0)))),
+                Box::new(mul(SymbolicExpr::Constant(4.0), mul(a.clone(), c))),
+            ).simplify();
+            let two_a = mul(SymbolicExpr::Constant(2.0), a);
+
+            if discriminant.is_zero() {
+                roots.push(div(neg(b), two_a).simplify());
+            } else {
+                let sqrt = SymbolicExpr::Function("sqrt".to_string(), vec![discriminant]);
+                roots.push(div(SymbolicExpr::Add(Box::new(neg(b.clone())), Box::new(sqrt.clone())), two_a.clone()).simplify());
+                roots.push(div(SymbolicExpr::Subtract(Box::new(neg(b)), Box::new(sqrt)), two_a).simplify());
+            }
+        }
+        degree => {
+            let bindings = HashMap::new();
+            let numeric: Vec<f64> = coeffs.iter()
+                .map(|coeff| coeff.eval(&bindings))
+                .collect::<Result<_, _>>()
+                .map_err(|_| TensorError::ComputationError(format!(
+                    "Cannot solve polynomial of degree {} in {} with symbolic coefficients", degree - 1, var
+                )))?;
+
+            for root in real_polynomial_roots(&numeric) {
+                if !roots.iter().any(|r: &SymbolicExpr| r.eval(&bindings).is_ok_and(|r| (r - root).abs() < 1e-7)) {
+                    roots.push(SymbolicExpr::Constant(root));
+                }
+            }
+        }
+    }
+
+    Ok(roots)
+}
+
+/// Real roots of `Σ coeffs[k] x^k` by Durand-Kerner iteration, sorted ascending.
+fn real_polynomial_roots(coeffs: &[f64]) -> Vec<f64> {
+    let degree = coeffs.len() - 1;
+    let leading = coeffs[degree];
+    let monic: Vec<Complex64> = coeffs.iter().map(|c| Complex64::new(c / leading, 0.0)).collect();
+    let evaluate = |x: Complex64| monic.iter().rev().fold(Complex64::new(0.0, 0.0), |acc, c| acc * x + c);
+
+    let seed = Complex64::new(0.4, 0.9);
+    let mut estimates: Vec<Complex64> = (0..degree).map(|k| seed.powu(k as u32)).collect();
+    for _ in 0..1000 {
+        let mut largest_step: f64 = 0.0;
+        for i in 0..degree {
+            let mut denominator = Complex64::new(1.0, 0.0);
+            for j in 0..degree {
+                if i != j {
+                    denominator *= estimates[i] - estimates[j];
+                }
+            }
+            let step = evaluate(estimates[i]) / denominator;
+            estimates[i] -= step;
+            largest_step = largest_step.max(step.norm());
+        }
+        if largest_step < 1e-14 {
+            break;
+        }
+    }
+
+    let mut real: Vec<f64> = estimates.iter()
+        .filter(|z| z.im.abs() < 1e-7 * z.re.abs().max(1.0))
+        .map(|z| z.re)
+        .collect();
+    real.sort_by(|a, b| a.total_cmp(b));
+    real.dedup_by(|a, b| (*a - *b).abs() < 1e-7);
+    real
+}
+
+/// How many times `root` divides the polynomial, by repeated synthetic division.
+fn root_multiplicity(coeffs: &[SymbolicExpr], root: &SymbolicExpr) -> usize {
+    let mut coeffs = poly_trim(coeffs.to_vec());
+    let mut multiplicity = 0;
+
+    while coeffs.len() > 1 {
+        // Highest power first: b_k = a_k + root * b_(k+1), remainder left in b_0
+        let mut quotient = vec![SymbolicExpr::Zero; coeffs.len() - 1];
+        let mut carry = SymbolicExpr::Zero;
+        for k in (0..coeffs.len()).rev() {
+            carry = SymbolicExpr::Add(
+                Box::new(coeffs[k].clone()),
+                Box::new(SymbolicExpr::Multiply(Box::new(root.clone()), Box::new(carry))),
+            ).simplify();
+            if k > 0 {
+                quotient[k - 1] = carry.clone();
+            }
+        }
+        if !carry.approx_eq(&SymbolicExpr::Zero) {
+            break;
+        }
+        multiplicity += 1;
+        coeffs = quotient;
+    }
+
+    multiplicity
+}
+
+fn poly_add(a: &[SymbolicExpr], b: &[SymbolicExpr]) -> Vec<SymbolicExpr> {
+    (0..a.len().max(b.len()))
+        .map(|k| match (a.get(k), b.get(k)) {
+            (Some(x), Some(y)) => SymbolicExpr::Add(Box::new(x.clone()), Box::new(y.clone())).simplify(),
+            (Some(x), None) | (None, Some(x)) => x.clone(),
+            (None, None) => SymbolicExpr::Zero,
+        })
+        .collect()
+}
+
+fn poly_sub(a: &[SymbolicExpr], b: &[SymbolicExpr]) -> Vec<SymbolicExpr> {
+    let negated: Vec<SymbolicExpr> = b.iter()
+        .map(|y| SymbolicExpr::Negate(Box::new(y.clone())))
+        .collect();
+    poly_add(a, &negated)
+}
+
+fn poly_mul(a: &[SymbolicExpr], b: &[SymbolicExpr]) -> Vec<SymbolicExpr> {
+    let mut product = vec![SymbolicExpr::Zero; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] = SymbolicExpr::Add(
+                Box::new(product[i + j].clone()),
+                Box::new(SymbolicExpr::Multiply(Box::new(x.clone()), Box::new(y.clone()))),
+            ).simplify();
+        }
+    }
+    product
+}
+
+/// Drops vanishing highest-order coefficients.
+fn poly_trim(mut coeffs: Vec<SymbolicExpr>) -> Vec<SymbolicExpr> {
+    while coeffs.len() > 1 && coeffs.last().is_some_and(|c| c.is_equivalent(&SymbolicExpr::Zero)) {
+        coeffs.pop();
+    }
+    coeffs
+}
+
+/// A product `coefficient * Π base^exponent`. Factors are kept sorted by the
+/// rendering of their base, which is stored alongside it as the sort key.
+#[derive(Debug, Clone)]
+struct Monomial {
+    coefficient: f64,
+    factors: Vec<(String, SymbolicExpr, f64)>,
+}
+
+impl Monomial {
+    fn constant(coefficient: f64) -> Self {
+        Monomial { coefficient, factors: Vec::new() }
+    }
+
+    fn factor(base: SymbolicExpr, exponent: f64) -> Self {
+        Monomial { coefficient: 1.0, factors: vec![(base.to_string(), base, exponent)] }
+    }
+
+    fn times(&self, other: &Monomial) -> Monomial {
+        let mut factors = self.factors.clone();
+        for (key, base, exponent) in &other.factors {
+            match factors.binary_search_by(|(k, _, _)| k.as_str().cmp(key)) {
+                Ok(i) => factors[i].2 += exponent,
+                Err(i) => factors.insert(i, (key.clone(), base.clone(), *exponent)),
+            }
+        }
+        factors.retain(|(_, _, exponent)| *exponent != 0.0);
+        Monomial { coefficient: self.coefficient * other.coefficient, factors }
+    }
+
+    fn pow(&self, exponent: f64) -> Monomial {
+        Monomial {
+            coefficient: self.coefficient.powf(exponent),
+            factors: self.factors.iter().map(|(k, b, e)| (k.clone(), b.clone(), e * exponent)).collect(),
+        }
+    }
+
+    /// Identifies the monomial up to its coefficient.
+    fn signature(&self) -> String {
+        self.factors.iter().map(|(key, _, exponent)| format!("{}^{};", key, exponent)).collect()
+    }
+
+    fn to_expr(&self) -> SymbolicExpr {
+        let product = |factors: Vec<SymbolicExpr>| {
+            factors.into_iter()
+                .reduce(|acc, factor| SymbolicExpr::Multiply(Box::new(acc), Box::new(factor)))
+                .unwrap_or(SymbolicExpr::One)
+        };
+        let power = |base: &SymbolicExpr, exponent: f64| {
+            if exponent == 1.0 {
+                base.clone()
+            } else {
+                SymbolicExpr::Power(Box::new(base.clone()), Box::new(SymbolicExpr::Constant(exponent)))
+            }
+        };
+        
+        let numerator = product(self.factors.iter().filter(|f| f.2 > 0.0).map(|(_, b, e)| power(b, *e)).collect());
+        let denominator = product(self.factors.iter().filter(|f| f.2 < 0.0).map(|(_, b, e)| power(b, -e)).collect());
+        
+        let numerator = match (self.coefficient, numerator) {
+            (c, SymbolicExpr::One) => SymbolicExpr::Constant(c),
+            (1.0, numerator) => numerator,
+            (c, numerator) => SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(c)), Box::new(numerator)),
+        };
+        match denominator {
+            SymbolicExpr::One => numerator,
+            denominator => SymbolicExpr::Divide(Box::new(numerator), Box::new(denominator)),
+        }
+    }
+}
+
+/// Expands `expr` into monomials, or returns `None` once more than `max_terms` would
+/// be produced. `trig` enables `sin(x)^2 + cos(x)^2 = 1` when collecting terms.
+fn expand_terms(expr: &SymbolicExpr, max_terms: usize, trig: bool) -> Option<Vec<Monomial>> {
+    let terms = match expr {
+        SymbolicExpr::Zero => Vec::new(),
+        SymbolicExpr::One => vec![Monomial::constant(1.0)],
+        SymbolicExpr::Constant(c) => vec![Monomial::constant(*c)],
+        SymbolicExpr::Variable(_) => vec![Monomial::factor(expr.clone(), 1.0)],
+        SymbolicExpr::Add(a, b) => {
+            let mut terms = expand_terms(a, max_terms, trig)?;
+            terms.extend(expand_terms(b, max_terms, trig)?);
+            terms
+        }
+        SymbolicExpr::Subtract(a, b) => {
+            let mut terms = expand_terms(a, max_terms, trig)?;
+            terms.extend(expand_terms(b, max_terms, trig)?.into_iter().map(|mut term| {
+                term.coefficient = -term.coefficient;
+                term
+            }));
+            terms
+        }
+        SymbolicExpr::Negate(a) => expand_terms(a, max_terms, trig)?.into_iter()
+            .map(|mut term| {
+                term.coefficient = -term.coefficient;
+                term
+            })
+            .collect(),
+        SymbolicExpr::Multiply(a, b) => {
+            multiply_terms(&expand_terms(a, max_terms, trig)?, &expand_terms(b, max_terms, trig)?, max_terms, trig)?
+        }
+        SymbolicExpr::Divide(a, b) => {
+            let numerator = expand_terms(a, max_terms, trig)?;
+            let reciprocal = power_of_terms(expand_terms(b, max_terms, trig)?, -1.0, max_terms, trig)?;
+            multiply_terms(&numerator, &reciprocal, max_terms, trig)?
+        }
+        SymbolicExpr::Power(base, exponent) => match exponent.constant_value() {
+            Some(n) => power_of_terms(expand_terms(base, max_terms, trig)?, n, max_terms, trig)?,
+            None => {
+                let base = rebuild_sum(expand_terms(base, max_terms, trig)?);
+                let exponent = rebuild_sum(expand_terms(exponent, max_terms, trig)?);
+                vec![Monomial::factor(SymbolicExpr::Power(Box::new(base), Box::new(exponent)), 1.0)]
+            }
+        },
+        SymbolicExpr::Function(name, args) => {
+            let args = args.iter()
+                .map(|arg| expand_terms(arg, max_terms, trig).map(rebuild_sum))
+                .collect::<Option<Vec<_>>>()?;
+            vec![Monomial::factor(SymbolicExpr::Function(name.clone(), args), 1.0)]
+        }
+    };
+    
+    let terms = collect_monomials(terms, trig);
+    (terms.len() <= max_terms).then_some(terms)
+}
+
+fn multiply_terms(a: &[Monomial], b: &[Monomial], max_terms: usize, trig: bool) -> Option<Vec<Monomial>> {
+    if a.len().saturating_mul(b.len()) > max_terms {
+        return None;
+    }
+    Some(collect_monomials(a.iter().flat_map(|x| b.iter().map(move |y| x.times(y))).collect(), trig))
+}
+
+/// Raises a sum of monomials to a numeric power. Small positive integer powers of sums
+/// are multiplied out; any other power of a sum becomes a single opaque factor, with
+/// the sum scaled so its first coefficient is 1 and the same sum always matches.
+fn power_of_terms(terms: Vec<Monomial>, exponent: f64, max_terms: usize, trig: bool) -> Option<Vec<Monomial>> {
+    const MAX_EXPANDED_POWER: f64 = 4.0;
+    
+    match terms.as_slice() {
+        [] if exponent > 0.0 => Some(Vec::new()),
+        [] if exponent == 0.0 => Some(vec![Monomial::constant(1.0)]),
+        // Division by zero stays symbolic, as in `simplify`
+        [] => Some(vec![Monomial::factor(SymbolicExpr::Zero, exponent)]),
+        [term] if term.coefficient > 0.0 || exponent.fract() == 0.0 => Some(vec![term.pow(exponent)]),
+        _ if exponent.fract() == 0.0 && (1.0..=MAX_EXPANDED_POWER).contains(&exponent) => {
+            let mut result = vec![Monomial::constant(1.0)];
+            for _ in 0..exponent as usize {
+                result = multiply_terms(&result, &terms, max_terms, trig)?;
+            }
+            Some(result)
+        }
+        _ => {
+            let scale = terms[0].coefficient;
+            if scale < 0.0 && exponent.fract() != 0.0 {
+                return Some(vec![Monomial::factor(rebuild_sum(terms), exponent)]);
+            }
+            let normalized = terms.into_iter().map(|mut term| {
+                term.coefficient /= scale;
+                term
+            }).collect();
+            let mut factor = Monomial::factor(rebuild_sum(normalized), exponent);
+            factor.coefficient = scale.powf(exponent);
+            Some(vec![factor])
+        }
+    }
+}
+
+/// Writes `expr` as a single quotient of expanded sums, `numerator / denominator`,
+/// by bringing every sum of fractions over a common denominator. Returns `None` once
+/// either side would exceed `max_terms`, or on division by zero.
+fn rational_terms(expr: &SymbolicExpr, max_terms: usize, trig: bool) -> Option<(Vec<Monomial>, Vec<Monomial>)> {
+    const MAX_RATIONAL_POWER: f64 = 8.0;
+    let one = || vec![Monomial::constant(1.0)];
+    
+    let (numerator, denominator) = match expr {
+        SymbolicExpr::Add(a, b) | SymbolicExpr::Subtract(a, b) => {
+            let (n1, d1) = rational_terms(a, max_terms, trig)?;
+            let (mut n2, d2) = rational_terms(b, max_terms, trig)?;
+            if matches!(expr, SymbolicExpr::Subtract(..)) {
+                n2.iter_mut().for_each(|term| term.coefficient = -term.coefficient);
+            }
+            if same_terms(&d1, &d2) {
+                (collect_monomials(n1.into_iter().chain(n2).collect(), trig), d1)
+            } else {
+                let left = multiply_terms(&n1, &d2, max_terms, trig)?;
+                let right = multiply_terms(&n2, &d1, max_terms, trig)?;
+                (collect_monomials(left.into_iter().chain(right).collect(), trig), multiply_terms(&d1, &d2, max_terms, trig)?)
+            }
+        }
+        SymbolicExpr::Negate(a) => {
+            let (mut n, d) = rational_terms(a, max_terms, trig)?;
+            n.iter_mut().for_each(|term| term.coefficient = -term.coefficient);
+            (n, d)
+        }
+        SymbolicExpr::Multiply(a, b) => {
+            let (n1, d1) = rational_terms(a, max_terms, trig)?;
+            let (n2, d2) = rational_terms(b, max_terms, trig)?;
+            (multiply_terms(&n1, &n2, max_terms, trig)?, multiply_terms(&d1, &d2, max_terms, trig)?)
+        }
+        SymbolicExpr::Divide(a, b) => {
+            let (n1, d1) = rational_terms(a, max_terms, trig)?;
+            let (n2, d2) = rational_terms(b, max_terms, trig)?;
+            if n2.is_empty() {
+                return None;
+            }
+            (multiply_terms(&n1, &d2, max_terms, trig)?, multiply_terms(&d1, &n2, max_terms, trig)?)
+        }
+        SymbolicExpr::Power(base, exponent) => match exponent.constant_value() {
+            Some(n) if n.fract() == 0.0 && n.abs() <= MAX_RATIONAL_POWER => {
+                let (mut numerator, mut denominator) = rational_terms(base, max_terms, trig)?;
+                if n < 0.0 {
+                    if numerator.is_empty() {
+                        return None;
+                    }
+                    std::mem::swap(&mut numerator, &mut denominator);
+                }
+                let (mut top, mut bottom) = (one(), one());
+                for _ in 0..n.abs() as usize {
+                    top = multiply_terms(&top, &numerator, max_terms, trig)?;
+                    bottom = multiply_terms(&bottom, &denominator, max_terms, trig)?;
+                }
+                (top, bottom)
+            }
+            _ => (expand_terms(expr, max_terms, trig)?, one()),
+        },
+        _ => (expand_terms(expr, max_terms, trig)?, one()),
+    };
+    
+    // A single-monomial denominator is folded into the numerator as negative powers
+    match denominator.as_slice() {
+        [term] => Some((multiply_terms(&numerator, &[term.pow(-1.0)], max_terms, trig)?, one())),
+        _ => Some((numerator, denominator)),
+    }
+}
+
+fn same_terms(a: &[Monomial], b: &[Monomial]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.coefficient == y.coefficient && x.signature() == y.signature())
+}
+
+/// Combines monomials with the same factors, dropping those that cancel, and with
+/// `trig` the Pythagorean pairs. The result is ordered by signature so that equal
+/// sums always come out the same way.
+fn collect_monomials(terms: Vec<Monomial>, trig: bool) -> Vec<Monomial> {
+    const CANCELLATION_TOLERANCE: f64 = 1e-12;
+    
+    // Each signature keeps the largest coefficient that went into it, so that a sum
+    // counts as cancelled only relative to the size of its terms: `1e-13*x` on its
+    // own survives, while `0.1*x + 0.2*x - 0.3*x` does not.
+    let mut collected: BTreeMap<String, (Monomial, f64)> = BTreeMap::new();
+    let insert = |collected: &mut BTreeMap<String, (Monomial, f64)>, term: Monomial, scale: f64| {
+        collected.entry(term.signature())
+            .and_modify(|(existing, existing_scale)| {
+                existing.coefficient += term.coefficient;
+                *existing_scale = existing_scale.max(scale);
+            })
+            .or_insert((term, scale));
+    };
+    for term in terms {
+        let scale = term.coefficient.abs();
+        insert(&mut collected, term, scale);
+    }
+    
+    // c * sin(x)^2 * m + c * cos(x)^2 * m = c * m, where m may contain any power
+    // of sin(x) or cos(x) itself
+    while trig && let Some((term_signature, partner_signature, reduced, scale)) = collected.iter().find_map(|(signature, (term, scale))| {
+        term.factors.iter().find_map(|(_, base, exponent)| match base {
+            SymbolicExpr::Function(name, args) if (name == "sin" || name == "cos") && args.len() == 1 && *exponent >= 2.0 => {
+                let other = if name == "sin" { "cos" } else { "sin" };
+                let complement = SymbolicExpr::Function(other.to_string(), args.clone());
+                let reduced = term.times(&Monomial::factor(base.clone(), -2.0));
+                let partner = reduced.times(&Monomial::factor(complement, 2.0));
+                collected.get(&partner.signature())
+                    .filter(|(p, _)| {
+                        let size = p.coefficient.abs().max(term.coefficient.abs());
+                        (p.coefficient - term.coefficient).abs() <= CANCELLATION_TOLERANCE * size
+                    })
+                    .map(|(_, partner_scale)| (signature.clone(), partner.signature(), reduced, scale.max(*partner_scale)))
+            }
+            _ => None,
+        })
+    }) {
+        collected.remove(&term_signature);
+        collected.remove(&partner_signature);
+        insert(&mut collected, reduced, scale);
+    }
+    
+    collected.into_values()
+        .filter(|(term, scale)| term.coefficient.abs() > CANCELLATION_TOLERANCE * scale)
+        .map(|(term, _)| term)
+        .collect()
+}
+
+fn rebuild_sum(terms: Vec<Monomial>) -> SymbolicExpr {
+    let mut result = SymbolicExpr::Zero;
+    for mut term in terms {
+        let negative = term.coefficient < 0.0;
+        term.coefficient = term.coefficient.abs();
+        result = match (result, negative) {
+            (SymbolicExpr::Zero, false) => term.to_expr(),
+            (SymbolicExpr::Zero, true) if term.factors.is_empty() => SymbolicExpr::Constant(-term.coefficient),
+            (SymbolicExpr::Zero, true) => SymbolicExpr::Negate(Box::new(term.to_expr())),
+            (acc, true) => SymbolicExpr::Subtract(Box::new(acc), Box::new(term.to_expr())),
+            (acc, false) => SymbolicExpr::Add(Box::new(acc), Box::new(term.to_expr())),
+        };
+    }
+    result
+}
+
+/// Asserts that two `SymbolicExpr`s are mathematically equal, using
+/// [`SymbolicExpr::approx_eq`].
+#[macro_export]
+macro_rules! assert_expr_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !$crate::SymbolicExpr::approx_eq(left, right) {
+                    panic!("assertion failed: `left ≈ right`\n  left: `{}`\n right: `{}`", left, right);
+                }
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Comma,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, TensorError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Scientific notation, e.g. 6.67e-11
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let val = text.parse::<f64>()
+                .map_err(|_| TensorError::ParseError(format!("Invalid number '{}'", text)))?;
+            tokens.push(Token::Number(val));
+        } else if c.is_alphabetic() || c == '_' {
+            // Names may carry primes and dots after the first character, e.g. a' or a.dot
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '\'' | '.')) {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(match c {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '/' => Token::Slash,
+                '^' => Token::Caret,
+                ',' => Token::Comma,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                _ => return Err(TensorError::ParseError(format!("Unexpected character '{}'", c))),
+            });
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Counts occurrences of each non-trivial sub-expression, keyed by its printed form.
+/// A repeat isn't descended into, so the parts of a repeated sub-expression only
+/// count as repeated if they also occur elsewhere.
+fn count_subexpressions(expr: &SymbolicExpr, counts: &mut HashMap<String, usize>) {
+    let children: Vec<&SymbolicExpr> = match expr {
+        SymbolicExpr::Add(left, right)
+        | SymbolicExpr::Subtract(left, right)
+        | SymbolicExpr::Multiply(left, right)
+        | SymbolicExpr::Divide(left, right)
+        | SymbolicExpr::Power(left, right) => vec![left, right],
+        SymbolicExpr::Negate(inner) => vec![inner],
+        SymbolicExpr::Function(_, args) => args.iter().collect(),
+        _ => return,
+    };
+    let count = counts.entry(expr.to_string()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        for child in children {
+            count_subexpressions(child, counts);
+        }
+    }
+}
+
+/// Shared state for extracting repeated sub-expressions of a set of expressions
+/// into bindings named `_cse<k>`.
+pub(crate) struct CseState {
+    counts: HashMap<String, usize>,
+    taken: BTreeSet<String>,
+    names: HashMap<String, String>,
+    /// Extracted sub-expressions in dependency order
+    pub(crate) bindings: Vec<(String, SymbolicExpr)>,
+}
+
+impl CseState {
+    pub(crate) fn new(exprs: &[SymbolicExpr]) -> Self {
+        let mut counts = HashMap::new();
+        for expr in exprs {
+            count_subexpressions(expr, &mut counts);
+        }
+        let taken = exprs.iter().flat_map(|expr| expr.free_symbols()).collect();
+        CseState { counts, taken, names: HashMap::new(), bindings: Vec::new() }
+    }
+    
+    /// Rewrites bottom-up, so a binding only refers to bindings created before it.
+    pub(crate) fn rewrite(&mut self, expr: &SymbolicExpr) -> SymbolicExpr {
+        let rewritten = match expr {
+            SymbolicExpr::Add(left, right) => SymbolicExpr::Add(Box::new(self.rewrite(left)), Box::new(self.rewrite(right))),
+            SymbolicExpr::Subtract(left, right) => SymbolicExpr::Subtract(Box::new(self.rewrite(left)), Box::new(self.rewrite(right))),
+            SymbolicExpr::Negate(inner) => SymbolicExpr::Negate(Box::new(self.rewrite(inner))),
+            SymbolicExpr::Multiply(left, right) => SymbolicExpr::Multiply(Box::new(self.rewrite(left)), Box::new(self.rewrite(right))),
+            SymbolicExpr::Divide(left, right) => SymbolicExpr::Divide(Box::new(self.rewrite(left)), Box::new(self.rewrite(right))),
+            SymbolicExpr::Power(base, exp) => SymbolicExpr::Power(Box::new(self.rewrite(base)), Box::new(self.rewrite(exp))),
+            SymbolicExpr::Function(name, args) => SymbolicExpr::Function(
+                name.clone(),
+                args.iter().map(|arg| self.rewrite(arg)).collect(),
+            ),
+            _ => return expr.clone(),
+        };
+        
+        let key = expr.to_string();
+        if self.counts.get(&key).copied().unwrap_or(0) < 2 {
+            return rewritten;
+        }
+        if let Some(name) = self.names.get(&key) {
+            return SymbolicExpr::Variable(name.clone());
+        }
+        
+        let mut index = self.bindings.len();
+        let name = loop {
+            let candidate = format!("_cse{}", index);
+            if !self.taken.contains(&candidate) {
+                break candidate;
+            }
+            index += 1;
+        };
+        self.taken.insert(name.clone());
+        self.names.insert(key, name.clone());
+        self.bindings.push((name.clone(), rewritten));
+        SymbolicExpr::Variable(name)
+    }
+}
+
+/// The common base and the two exponents of `x^a` and `x^b`, where a bare `x`
+/// counts as `x^1`. Returns `None` unless at least one side is a power and the
+/// bases match structurally.
+fn power_operands(left: &SymbolicExpr, right: &SymbolicExpr) -> Option<(SymbolicExpr, SymbolicExpr, SymbolicExpr)> {
+    let split = |expr: &SymbolicExpr| match expr {
+        SymbolicExpr::Power(base, exp) => ((**base).clone(), (**exp).clone()),
+        other => (other.clone(), SymbolicExpr::One),
+    };
+    if !matches!(left, SymbolicExpr::Power(..)) && !matches!(right, SymbolicExpr::Power(..)) {
+        return None;
+    }
+    let (left_base, a) = split(left);
+    let (right_base, b) = split(right);
+    (left_base == right_base).then_some((left_base, a, b))
+}
+
+/// Cancels factors shared by a numerator and denominator, comparing the bases of
+/// numeric powers so that `r^2 / r = r` and `(a*b) / (b*c) = a / c`. Returns `None`
+/// when the two have no factor in common.
+fn cancel_common_factors(numerator: &SymbolicExpr, denominator: &SymbolicExpr) -> Option<SymbolicExpr> {
+    let (mut upper_coefficient, mut lower_coefficient) = (1.0, 1.0);
+    let mut upper = Vec::new();
+    let mut lower = Vec::new();
+    collect_factors(numerator, 1.0, &mut upper_coefficient, &mut upper);
+    collect_factors(denominator, -1.0, &mut lower_coefficient, &mut lower);
+    
+    let shared_base = upper.iter().any(|(base, _)| lower.iter().any(|(other, _)| base == other));
+    let shared_constant = upper_coefficient != 1.0 && lower_coefficient != 1.0;
+    let coefficient = upper_coefficient * lower_coefficient;
+    if coefficient == 0.0 || !coefficient.is_finite() || !(shared_base || shared_constant) {
+        return None;
+    }
+    
+    // Net exponent of each base; the denominator's were collected negated
+    let mut factors: Vec<(SymbolicExpr, f64)> = Vec::new();
+    for (base, exponent) in upper.into_iter().chain(lower) {
+        match factors.iter_mut().find(|(existing, _)| *existing == base) {
+            Some(entry) => entry.1 += exponent,
+            None => factors.push((base, exponent)),
+        }
+    }
+    
+    let product = |factors: Vec<SymbolicExpr>| {
+        factors.into_iter()
+            .reduce(|acc, factor| SymbolicExpr::Multiply(Box::new(acc), Box::new(factor)))
+            .unwrap_or(SymbolicExpr::One)
+    };
+    let power = |base: &SymbolicExpr, exponent: f64| {
+        if exponent == 1.0 {
+            base.clone()
+        } else {
+            SymbolicExpr::Power(Box::new(base.clone()), Box::new(SymbolicExpr::Constant(exponent)))
+        }
+    };
+    
+    let top = product(factors.iter().filter(|(_, e)| *e > 0.0).map(|(b, e)| power(b, *e)).collect());
+    let bottom = product(factors.iter().filter(|(_, e)| *e < 0.0).map(|(b, e)| power(b, -e)).collect());
+    let top = match top {
+        SymbolicExpr::One => SymbolicExpr::Constant(coefficient),
+        top if coefficient == 1.0 => top,
+        top => SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(coefficient)), Box::new(top)),
+    };
+    
+    Some(match bottom {
+        SymbolicExpr::One => top,
+        bottom => SymbolicExpr::Divide(Box::new(top), Box::new(bottom)),
+    })
+}
+
+/// Flattens products, quotients and negations into a numeric coefficient and
+/// `(base, exponent)` factors, with `exponent` applied to everything found.
+fn collect_factors(expr: &SymbolicExpr, exponent: f64, coefficient: &mut f64, factors: &mut Vec<(SymbolicExpr, f64)>) {
+    match expr {
+        SymbolicExpr::Multiply(a, b) => {
+            collect_factors(a, exponent, coefficient, factors);
+            collect_factors(b, exponent, coefficient, factors);
+        }
+        SymbolicExpr::Divide(a, b) => {
+            collect_factors(a, exponent, coefficient, factors);
+            collect_factors(b, -exponent, coefficient, factors);
+        }
+        SymbolicExpr::Negate(inner) => {
+            *coefficient = -*coefficient;
+            collect_factors(inner, exponent, coefficient, factors);
+        }
+        SymbolicExpr::Subtract(zero, inner) if **zero == SymbolicExpr::Zero => {
+            *coefficient = -*coefficient;
+            collect_factors(inner, exponent, coefficient, factors);
+        }
+        SymbolicExpr::Power(base, power) if power.constant_value().is_some() => {
+            let power = power.constant_value().unwrap_or(1.0);
+            factors.push(((**base).clone(), power * exponent));
+        }
+        _ => match expr.constant_value() {
+            Some(c) => *coefficient *= c.powf(exponent),
+            None => factors.push((expr.clone(), exponent)),
+        },
+    }
+}
+
+/// Splits `sin(x)^2`, `cos(x)^2` or a product with one of them as a direct factor
+/// into the function name, `x` and the remaining factor.
+fn split_trig_square(term: &SymbolicExpr) -> Option<(&'static str, SymbolicExpr, SymbolicExpr)> {
+    let square = |expr: &SymbolicExpr| match expr {
+        SymbolicExpr::Power(base, exp) if exp.constant_value() == Some(2.0) => match &**base {
+            SymbolicExpr::Function(name, args) if args.len() == 1 && name == "sin" => Some(("sin", args[0].clone())),
+            SymbolicExpr::Function(name, args) if args.len() == 1 && name == "cos" => Some(("cos", args[0].clone())),
+            _ => None,
+        },
+        _ => None,
+    };
+    
+    if let Some((name, arg)) = square(term) {
+        return Some((name, arg, SymbolicExpr::One));
+    }
+    match term {
+        SymbolicExpr::Multiply(a, b) => square(a)
+            .map(|(name, arg)| (name, arg, (**b).clone()))
+            .or_else(|| square(b).map(|(name, arg)| (name, arg, (**a).clone()))),
+        _ => None,
+    }
+}
+
+/// Number of top-level terms in a sum, counting the `0` of a negation `0 - x` as none.
+fn count_additive_terms(expr: &SymbolicExpr) -> usize {
+    match expr {
+        SymbolicExpr::Add(a, b) | SymbolicExpr::Subtract(a, b) => count_additive_terms(a) + count_additive_terms(b),
+        SymbolicExpr::Negate(inner) => count_additive_terms(inner),
+        SymbolicExpr::Zero => 0,
+        _ => 1,
+    }
+}
+
+/// Recursive-descent parser for arithmetic expressions.
+///
+/// Grammar (lowest to highest precedence):
+/// ```text
+/// expr  := term (('+' | '-') term)*
+/// term  := unary (('*' | '/') unary | power)*
+/// unary := ('-' | '+') unary | power
+/// power := atom ('^' unary)?
+/// atom  := number | ident | ident '(' args ')' | '(' expr ')'
+/// ```
+///
+/// A factor followed directly by an identifier or `(` is an implicit product, so
+/// `2M/r` parses as `2*M/r`. An identifier followed by `(` is still a function
+/// application: `a(t)` is the function `a`, not `a*t`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, TensorError> {
+        Ok(Parser { tokens: tokenize(input)?, pos: 0 })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), TensorError> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            other => Err(TensorError::ParseError(format!("Expected {:?}, found {:?}", expected, other))),
+        }
+    }
+
+    fn parse_all(&mut self) -> Result<SymbolicExpr, TensorError> {
+        let expr = self.parse_expr()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(token) => Err(TensorError::ParseError(format!("Unexpected token {:?}", token))),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<SymbolicExpr, TensorError> {
+        let mut left = self.parse_term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.next();
+                    let right = self.parse_term()?;
+                    left = SymbolicExpr::Add(Box::new(left), Box::new(right));
+                }
+                Some(Token::Minus) => {
+                    self.next();
+                    let right = self.parse_term()?;
+                    left = SymbolicExpr::Subtract(Box::new(left), Box::new(right));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<SymbolicExpr, TensorError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.next();
+                    let right = self.parse_unary()?;
+                    left = SymbolicExpr::Multiply(Box::new(left), Box::new(right));
+                }
+                Some(Token::Slash) => {
+                    self.next();
+                    let right = self.parse_unary()?;
+                    left = SymbolicExpr::Divide(Box::new(left), Box::new(right));
+                }
+                // Implicit multiplication, e.g. 2M, 2(r + 1), (a+b)(c+d) or r sin(theta)
+                Some(Token::Ident(_)) | Some(Token::LParen) => {
+                    let right = self.parse_power()?;
+                    left = SymbolicExpr::Multiply(Box::new(left), Box::new(right));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<SymbolicExpr, TensorError> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next();
+                let operand = self.parse_unary()?;
+                Ok(match operand {
+                    SymbolicExpr::Constant(val) => SymbolicExpr::Constant(-val),
+                    SymbolicExpr::One => SymbolicExpr::Constant(-1.0),
+                    SymbolicExpr::Zero => SymbolicExpr::Zero,
+                    expr => SymbolicExpr::Negate(Box::new(expr)),
+                })
+            }
+            Some(Token::Plus) => {
+                self.next();
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<SymbolicExpr, TensorError> {
+        let base = self.parse_atom()?;
+        if let Some(Token::Caret) = self.peek() {
+            self.next();
+            let exp = self.parse_unary()?;
+            // x^(1/2) and x^0.5 are read as sqrt(x)
+            if exp.simplify().constant_value() == Some(0.5) {
+                return Ok(SymbolicExpr::Function("sqrt".to_string(), vec![base]));
+            }
+            return Ok(SymbolicExpr::Power(Box::new(base), Box::new(exp)));
+        }
+        Ok(base)
+    }
+
+    fn parse_atom(&mut self) -> Result<SymbolicExpr, TensorError> {
+        match self.next() {
+            Some(Token::Number(val)) => Ok(if val == 0.0 {
+                SymbolicExpr::Zero
+            } else if val == 1.0 {
+                SymbolicExpr::One
+            } else {
+                SymbolicExpr::Constant(val)
+            }),
+            Some(Token::Ident(name)) => {
+                if let Some(Token::LParen) = self.peek() {
+                    self.next();
+                    let mut args = Vec::new();
+                    if let Some(Token::RParen) = self.peek() {
+                        self.next();
+                    } else {
+                        loop {
+                            args.push(self.parse_expr()?);
+                            match self.next() {
+                                Some(Token::Comma) => continue,
+                                Some(Token::RParen) => break,
+                                other => return Err(TensorError::ParseError(
+                                    format!("Expected ',' or ')' in arguments of {}, found {:?}", name, other)
+                                )),
+                            }
+                        }
+                    }
+                    Ok(SymbolicExpr::Function(name, args))
+                } else {
+                    Ok(SymbolicExpr::Variable(name))
+                }
+            }
+            Some(Token::LParen) => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            other => Err(TensorError::ParseError(format!("Unexpected token {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_parse_simple() {
+        assert_eq!(SymbolicExpr::parse("x").unwrap(), SymbolicExpr::Variable("x".to_string()));
+        assert_eq!(SymbolicExpr::parse("42").unwrap(), SymbolicExpr::Constant(42.0));
+        assert_eq!(SymbolicExpr::parse("0").unwrap(), SymbolicExpr::Zero);
+        assert_eq!(SymbolicExpr::parse("1").unwrap(), SymbolicExpr::One);
+    }
+
+    #[test]
+    fn test_parse_power() {
+        match SymbolicExpr::parse("r^2").unwrap() {
+            SymbolicExpr::Power(base, exp) => {
+                assert_eq!(*base, SymbolicExpr::Variable("r".to_string()));
+                assert_eq!(*exp, SymbolicExpr::Constant(2.0));
+            }
+            _ => panic!("Expected power expression"),
+        }
+    }
+
+    #[test]
+    fn test_parse_arithmetic() {
+        let expr = SymbolicExpr::parse("-(1 - 2*M/r)").unwrap();
+        assert_eq!(expr.to_string(), "-(1 - ((2 * M) / r))");
+
+        let expr = SymbolicExpr::parse("r^2 * sin(theta)^2").unwrap();
+        assert_eq!(expr.to_string(), "(r^2 * sin(theta)^2)");
+
+        // Display output parses back to the same tree
+        let expr = SymbolicExpr::parse("1/(1 - 2*M/r + Q^2/r^2)").unwrap();
+        assert_eq!(SymbolicExpr::parse(&expr.to_string()).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_parse_unary_minus() {
+        let neg = |expr: SymbolicExpr| SymbolicExpr::Negate(Box::new(expr));
+        let var = |name: &str| SymbolicExpr::Variable(name.to_string());
+
+        assert_eq!(SymbolicExpr::parse("-1").unwrap(), SymbolicExpr::Constant(-1.0));
+        assert_eq!(SymbolicExpr::parse("-2.5").unwrap(), SymbolicExpr::Constant(-2.5));
+        assert_eq!(SymbolicExpr::parse("-r").unwrap(), neg(var("r")));
+        assert_eq!(
+            SymbolicExpr::parse("-r^2").unwrap(),
+            neg(SymbolicExpr::Power(Box::new(var("r")), Box::new(SymbolicExpr::Constant(2.0))))
+        );
+        assert_eq!(
+            SymbolicExpr::parse("-(a+b)").unwrap(),
+            neg(SymbolicExpr::Add(Box::new(var("a")), Box::new(var("b"))))
+        );
+
+        // The sign binds to the first factor, not the whole product
+        assert_eq!(SymbolicExpr::parse("-2*M/r").unwrap().to_string(), "((-2 * M) / r)");
+
+        for input in ["--x", "- -x", "-(-x)"] {
+            assert_eq!(SymbolicExpr::parse(input).unwrap().simplify(), var("x"));
+        }
+        assert_eq!(SymbolicExpr::parse("a - -b").unwrap().simplify().to_string(), "(a + b)");
+        assert_eq!(SymbolicExpr::parse("a + -b").unwrap().simplify().to_string(), "(a - b)");
+    }
+
+    #[test]
+    fn test_negate() {
+        let neg = |expr: SymbolicExpr| SymbolicExpr::Negate(Box::new(expr));
+        let var = |name: &str| SymbolicExpr::Variable(name.to_string());
+
+        // Display parses back to the same tree
+        let expr = SymbolicExpr::parse("-r^2 / -(a + b)").unwrap();
+        assert_eq!(expr.to_string(), "(-r^2 / -(a + b))");
+        assert_eq!(SymbolicExpr::parse(&expr.to_string()).unwrap(), expr);
+
+        assert_eq!(neg(neg(var("x"))).simplify(), var("x"));
+        assert_eq!(neg(SymbolicExpr::Zero).simplify(), SymbolicExpr::Zero);
+        assert_eq!(neg(SymbolicExpr::Constant(2.0)).simplify(), SymbolicExpr::Constant(-2.0));
+        assert_eq!(SymbolicExpr::parse("0 - x").unwrap().simplify(), neg(var("x")));
+        assert_eq!(SymbolicExpr::parse("-x + y").unwrap().simplify().to_string(), "(y - x)");
+
+        assert_eq!(SymbolicExpr::parse("-x^3").unwrap().derivative("x").simplify().to_string(), "-(3 * x^2)");
+        assert_eq!(SymbolicExpr::parse("cos(x)").unwrap().derivative("x").simplify(), neg(SymbolicExpr::parse("sin(x)").unwrap()));
+        assert_eq!(neg(var("x")).eval(&HashMap::from([("x".to_string(), 2.0)])).unwrap(), -2.0);
+    }
+
+    #[test]
+    fn test_parse_implicit_multiplication() {
+        let same = |implicit: &str, explicit: &str| {
+            assert_eq!(SymbolicExpr::parse_strict(implicit).unwrap(), SymbolicExpr::parse_strict(explicit).unwrap());
+        };
+        same("2r", "2*r");
+        same("2M/r", "2*M/r");
+        same("1 - 2M/r", "1 - 2*M/r");
+        same("2r^2", "2*r^2");
+        same("2(r + 1)", "2*(r + 1)");
+        same("(a+b)(c+d)", "(a+b)*(c+d)");
+        same("r sin(theta)", "r*sin(theta)");
+        same("6.67e-11M", "6.67e-11*M");
+
+        // An identifier followed by a parenthesis stays a function application
+        assert_eq!(
+            SymbolicExpr::parse("r(r+1)").unwrap(),
+            SymbolicExpr::Function("r".to_string(), vec![SymbolicExpr::parse("r+1").unwrap()])
+        );
+        assert_eq!(SymbolicExpr::parse("Omega_m").unwrap(), SymbolicExpr::Variable("Omega_m".to_string()));
+        same("3Omega_m", "3*Omega_m");
+    }
+
+    #[test]
+    fn test_simplify_collects_like_terms() {
+        let simplified = |input: &str| SymbolicExpr::parse(input).unwrap().simplify().to_string();
+        assert_eq!(simplified("x + x"), "(2 * x)");
+        assert_eq!(simplified("2*a + 3*a"), "(5 * a)");
+        assert_eq!(simplified("3*r - r"), "(2 * r)");
+        assert_eq!(simplified("r - r"), "0");
+        assert_eq!(simplified("x + y - x"), "y");
+        assert_eq!(simplified("x - y - x"), "-y");
+        assert_eq!(simplified("1 + x + 2 + x"), "(3 + (2 * x))");
+        assert_eq!(simplified("sin(t)^2 + 2*sin(t)^2 - M/r"), "((3 * sin(t)^2) - (M / r))");
+
+        // Nothing to combine: the sum keeps its shape
+        assert_eq!(simplified("a + b - 2*c"), "((a + b) - (2 * c))");
+    }
+
+    #[test]
+    fn test_simplify_self_subtraction() {
+        for input in ["x - x", "sin(theta) - sin(theta)", "(r^2 * sin(theta)^2) - (r^2 * sin(theta)^2)", "(a + b) - (a + b)"] {
+            assert_eq!(SymbolicExpr::parse(input).unwrap().simplify(), SymbolicExpr::Zero, "{}", input);
+        }
+
+        // Equal once their operands are simplified, and inside larger sums
+        assert_eq!(SymbolicExpr::parse("(1 * x) - (x + 0)").unwrap().simplify(), SymbolicExpr::Zero);
+        assert_eq!(SymbolicExpr::parse("y + cos(t) - cos(t)").unwrap().simplify().to_string(), "y");
+        assert_eq!(SymbolicExpr::parse("sin(t) - cos(t)").unwrap().simplify().to_string(), "(sin(t) - cos(t))");
+    }
+
+    #[test]
+    fn test_simplify_negative_subtrahends() {
+        let simplified = |src: &str| SymbolicExpr::parse(src).unwrap().simplify().to_string();
+
+        assert_eq!(simplified("x - (-2)"), "(x + 2)");
+        assert_eq!(simplified("a - (-2*b)"), "(a + (2 * b))");
+        assert_eq!(simplified("a - (-1*b)"), "(a + b)");
+        assert_eq!(simplified("x - (0 - y)"), "(x + y)");
+        assert_eq!(simplified("0 - (0 - x)"), "x");
+        assert_eq!(simplified("0 - (-2*b)"), "(2 * b)");
+        assert_eq!(SymbolicExpr::parse("0 - 3").unwrap().simplify(), SymbolicExpr::Constant(-3.0));
+
+        // Positive subtrahends are left alone
+        assert_eq!(simplified("x - 2*b"), "(x - (2 * b))");
+    }
+
+    #[test]
+    fn test_simplify_pythagorean_identity() {
+        let simplified = |input: &str| SymbolicExpr::parse(input).unwrap().simplify().to_string();
+        assert_eq!(simplified("sin(theta)^2 + cos(theta)^2"), "1");
+        assert_eq!(simplified("cos(theta)^2 + sin(theta)^2"), "1");
+        assert_eq!(simplified("r^2*(sin(theta)^2 + cos(theta)^2)"), "r^2");
+        assert_eq!(simplified("x + sin(2*t)^2 - y + cos(2*t)^2"), "((x + 1) - y)");
+        assert_eq!(simplified("3*sin(t)^2 + 3*cos(t)^2"), "3");
+        assert_eq!(simplified("r^2 * sin(t)^2 + cos(t)^2 * r^2"), "r^2");
+
+        // Mismatched arguments or coefficients are left alone
+        assert_eq!(simplified("sin(t)^2 + cos(s)^2"), "(sin(t)^2 + cos(s)^2)");
+        assert_eq!(simplified("2*sin(t)^2 + cos(t)^2"), "((2 * sin(t)^2) + cos(t)^2)");
+
+        // The expanded form uses the identity too
+        let expanded = SymbolicExpr::parse("r^2*sin(t)^4 + r^2*sin(t)^2*cos(t)^2 - r^2*sin(t)^2").unwrap().expand();
+        assert_eq!(expanded, SymbolicExpr::Zero);
+        let expanded = SymbolicExpr::parse("1 + cos(t)^2 / sin(t)^2 - 1 / sin(t)^2").unwrap().expand();
+        assert_eq!(expanded, SymbolicExpr::Zero);
+    }
+
+    #[test]
+    fn test_simplify_folds_nested_constants() {
+        let simplified = |input: &str| SymbolicExpr::parse(input).unwrap().simplify().to_string();
+        assert_eq!(simplified("2 * (3 * x)"), "(6 * x)");
+        assert_eq!(simplified("(2 * 3) * x"), "(6 * x)");
+        assert_eq!(simplified("2 * 3 * x"), "(6 * x)");
+        assert_eq!(simplified("(x * 2) * 3"), "(6 * x)");
+        assert_eq!(simplified("2 * (3 * (4 * (5 * x)))"), "(120 * x)");
+        assert_eq!(simplified("(2 * x) * (3 * y)"), "(6 * (x * y))");
+        assert_eq!(simplified("1 + (2 + (3 + x))"), "(6 + x)");
+        assert_eq!(simplified("(1 + 2) * (3 + 4) * x"), "(21 * x)");
+
+        // Already simplified expressions are a fixed point
+        let expr = SymbolicExpr::parse("2 * (3 * (r + 1)) - M / r").unwrap().simplify();
+        assert_eq!(expr.simplify(), expr);
+    }
+
+    #[test]
+    fn test_simplify_cancels_common_factors() {
+        let simplified = |input: &str| SymbolicExpr::parse(input).unwrap().simplify().to_string();
+        assert_eq!(simplified("r^2 / r"), "r");
+        assert_eq!(simplified("r / r^3"), "(1 / r^2)");
+        assert_eq!(simplified("(a*b) / a"), "b");
+        assert_eq!(simplified("(a*b) / b"), "a");
+        assert_eq!(simplified("x / x"), "1");
+        assert_eq!(simplified("(a*b) / (b*c)"), "(a / c)");
+        assert_eq!(simplified("(2 * r^2 * sin(theta)) / (4 * r)"), "(0.5 * (r * sin(theta)))");
+        assert_eq!(simplified("-(1 - x) * r^2 / ((0 - (1 - x)) * (r^2 / (1 - x)))"), "(1 - x)");
+        assert_eq!(simplified("(1 - x) * (y / (1 - x)^2)"), "(y / (1 - x))");
+
+        // Nothing in common, or a zero denominator: left alone
+        assert_eq!(simplified("a / b"), "(a / b)");
+        assert_eq!(simplified("(a*b) / (a - a)"), "((a * b) / 0)");
+    }
+
+    #[test]
+    fn test_expand() {
+        let expanded = |input: &str| SymbolicExpr::parse(input).unwrap().expand();
+        assert_eq!(expanded("x*(a + b) - b*x - a*x"), SymbolicExpr::Zero);
+        assert_eq!(expanded("(a + b)^2 - a^2 - b^2").to_string(), "(2 * (a * b))");
+        assert_eq!(expanded("(r^2 * sin(theta)) / r").to_string(), "(r * sin(theta))");
+        assert_eq!(expanded("cos(theta) * cos(theta) / sin(theta) - cos(theta)^2 / sin(theta)"), SymbolicExpr::Zero);
+        assert_eq!(expanded("2 / (2 - 2*x) - 1 / (1 - x)"), SymbolicExpr::Zero);
+        crate::assert_expr_eq!(expanded("(1 + x) * (1 - x)"), SymbolicExpr::parse("1 - x^2").unwrap());
+        assert!(matches!(expanded("1 / (x - x)"), SymbolicExpr::Divide(..)));
+        
+        // Cancellation is judged relative to the terms that were combined
+        crate::assert_expr_eq!(expanded("1e-13*x + 1e-13*x^2"), SymbolicExpr::parse("1e-13*x + 1e-13*x^2").unwrap());
+        assert_eq!(expanded("0.1*x*y + 0.2*y*x - 0.3*x*y"), SymbolicExpr::Zero);
+        assert_eq!(expanded("1e-13*sin(x)^2 + 1e-13*cos(x)^2"), SymbolicExpr::Constant(1e-13));
+        assert!(!expanded("1e-13*sin(x)^2 + 2e-13*cos(x)^2").free_symbols().is_empty());
+        
+        // Cancellation that only shows over a common denominator
+        let fractions = SymbolicExpr::parse("1/(1 - 2*M/r) - r/(r - 2*M)").unwrap();
+        assert!(!fractions.expand().is_zero());
+        assert_eq!(fractions.simplify_expanded(), SymbolicExpr::Zero);
+        assert!(!SymbolicExpr::parse("1/(1 - 2*M/r) - 1/(r - 2*M)").unwrap().simplify_expanded().is_zero());
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        for input in ["1 +", "", "sin(x", "2 * * x", "x $ y"] {
+            match SymbolicExpr::parse_strict(input) {
+                Err(TensorError::ParseError(_)) => {}
+                other => panic!("Expected ParseError for {:?}, got {:?}", input, other),
+            }
+        }
+        assert_eq!(SymbolicExpr::parse("1 +").unwrap(), SymbolicExpr::Variable("1 +".to_string()));
+
+        // Valid compound expressions parse the same either way
+        for input in ["-(1 - 2*M/r)", "r^2 * sin(theta)^2", "a''(t) / a(t)", "6.67e-11 * M"] {
+            assert_eq!(SymbolicExpr::parse_strict(input).unwrap(), SymbolicExpr::parse(input).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_decorated_names() {
+        assert_eq!(SymbolicExpr::parse("a'").unwrap(), SymbolicExpr::Variable("a'".to_string()));
+        assert_eq!(SymbolicExpr::parse("H_0").unwrap(), SymbolicExpr::Variable("H_0".to_string()));
+        assert_eq!(SymbolicExpr::parse("a.dot").unwrap(), SymbolicExpr::Variable("a.dot".to_string()));
+
+        let expr = SymbolicExpr::parse("a''(t) / a(t)").unwrap();
+        assert_eq!(expr.to_string(), "(a''(t) / a(t))");
+        assert_eq!(SymbolicExpr::parse(&expr.to_string()).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_free_symbols() {
+        let g_tt = SymbolicExpr::parse("-(1 - 2*M/r)").unwrap();
+        let expected: BTreeSet<String> = ["M", "r"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(g_tt.free_symbols(), expected);
+
+        let expr = SymbolicExpr::parse("a(t)^2 * sin(pi * x)").unwrap();
+        let expected: BTreeSet<String> = ["a", "t", "x"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(expr.free_symbols(), expected);
+    }
+
+    #[test]
+    fn test_substitute() {
+        let expr = SymbolicExpr::parse("r^2 + r").unwrap();
+        assert_eq!(expr.substitute("r", &SymbolicExpr::Constant(2.0)).simplify(), SymbolicExpr::Constant(6.0));
+
+        let expr = SymbolicExpr::parse("sin(theta) * r").unwrap();
+        let substituted = expr.substitute("theta", &SymbolicExpr::parse("2*phi").unwrap());
+        assert_eq!(substituted.to_string(), "(sin((2 * phi)) * r)");
+
+        let expr = SymbolicExpr::parse("r * (M * (r + f(r, theta)))").unwrap();
+        let substituted = expr.substitute("r", &SymbolicExpr::parse("x + 1").unwrap());
+        assert!(!substituted.contains_variable("r"));
+        assert_eq!(substituted.to_string(), "((x + 1) * (M * ((x + 1) + f((x + 1), theta))))");
+
+        let expr = SymbolicExpr::parse("2 * (r * (r * M))").unwrap();
+        assert_eq!(
+            expr.substitute_and_simplify("r", &SymbolicExpr::Constant(3.0)),
+            SymbolicExpr::parse("18 * M").unwrap().simplify()
+        );
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let equivalent = |a: &str, b: &str| SymbolicExpr::parse(a).unwrap().is_equivalent(&SymbolicExpr::parse(b).unwrap());
+        assert!(equivalent("a+b", "b+a"));
+        assert!(equivalent("2*x", "x*2"));
+        assert!(equivalent("(a+b)+c", "a+(b+c)"));
+        assert!(equivalent("a*b*c", "c*(b*a)"));
+        assert!(equivalent("x*y + z", "z + y*x"));
+        assert!(equivalent("sin(x*y)", "sin(y*x)"));
+        assert!(equivalent("(a+b)^2 - c", "-c + (b+a)^2"));
+        assert!(!equivalent("a-b", "b-a"));
+        assert!(!equivalent("x/y", "y/x"));
+        
+        // Equal only over a common denominator
+        assert!(equivalent("1/(1 - 2*M/r)", "r/(r - 2*M)"));
+        assert!(equivalent("2 / (2 - 2*x)", "1 / (1 - x)"));
+        assert!(equivalent("sin(x)^2 + cos(x)^2", "1"));
+        assert!(!equivalent("1/(1 - 2*M/r)", "1/(r - 2*M)"));
+    }
+    
+    #[test]
+    fn test_canonicalize() {
+        let canonical = |input: &str| SymbolicExpr::parse(input).unwrap().canonicalize();
+        for input in ["a+b", "c*(b*a)*2", "x - (y - 3) + 1", "-(x*y) + sin(b+a)^2", "1/(r*2 - M*4)", "0*x + y*1"] {
+            let once = canonical(input);
+            assert_eq!(once.canonicalize(), once, "{}", input);
+        }
+        
+        assert_eq!(canonical("a+b"), canonical("b+a"));
+        assert_eq!(canonical("(a+b)+c"), canonical("c+(b+a)"));
+        assert_eq!(canonical("x*2*y"), canonical("2*(y*x)"));
+        assert_eq!(canonical("x*2*y").to_string(), "((2 * x) * y)");
+        assert_eq!(canonical("x - (y - 3) + 1").to_string(), "((4 + (-1 * y)) + x)");
+        assert_eq!(canonical("0*x + y*1"), SymbolicExpr::Variable("y".to_string()));
+        assert_ne!(canonical("a-b"), canonical("b-a"));
+    }
+    
+    #[test]
+    fn test_common_subexpressions() {
+        // Γ^r_φφ of Schwarzschild, where sin(θ)^2 appears twice
+        let gamma = SymbolicExpr::parse("2*M*sin(theta)^2 - r*sin(theta)^2").unwrap();
+        let (rewritten, bindings) = gamma.common_subexpressions();
+        
+        assert_eq!(bindings.len(), 1, "{:?}", bindings);
+        assert_eq!(bindings[0].1, SymbolicExpr::parse("sin(theta)^2").unwrap());
+        assert_eq!(rewritten, SymbolicExpr::parse("2*M*_cse0 - r*_cse0").unwrap());
+        
+        // (1 - 2M/r)^2 is extracted whole, and substituting it back restores the original
+        let lapse = SymbolicExpr::parse("(1 - 2*M/r)^2 / (r^2 * (1 - 2*M/r)^2 + (1 - 2*M/r)^2)").unwrap();
+        let (mut restored, bindings) = lapse.common_subexpressions();
+        assert_eq!(bindings.len(), 1);
+        for (name, definition) in bindings.iter().rev() {
+            restored = restored.substitute(name, definition);
+        }
+        assert_eq!(restored, lapse);
+        
+        let (unchanged, bindings) = SymbolicExpr::parse("x + y").unwrap().common_subexpressions();
+        assert!(bindings.is_empty());
+        assert_eq!(unchanged, SymbolicExpr::parse("x + y").unwrap());
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let hash = |input: &str| SymbolicExpr::parse(input).unwrap().structural_hash();
+        assert_eq!(hash("a+b"), hash("b+a"));
+        assert_eq!(hash("x*y*2 + z"), hash("z + 2*(y*x)"));
+        assert_eq!(hash("sin(a+b)"), hash("sin(b+a)"));
+        assert_eq!(hash("x + 0"), hash("x"));
+        assert_ne!(hash("a-b"), hash("b-a"));
+        assert_ne!(hash("x^2"), hash("x^3"));
+        
+        // Hashes agree wherever `is_equivalent` does
+        for (a, b) in [("1/(1 - 2*M/r)", "r/(r - 2*M)"), ("(a + b)^2", "a^2 + 2*a*b + b^2"), ("x/x", "1")] {
+            assert!(SymbolicExpr::parse(a).unwrap().is_equivalent(&SymbolicExpr::parse(b).unwrap()));
+            assert_eq!(hash(a), hash(b), "{} vs {}", a, b);
+        }
+        
+        let mut cache = HashMap::new();
+        cache.insert(hash("r^2*sin(theta)"), "cached");
+        assert_eq!(cache.get(&hash("sin(theta)*r^2")), Some(&"cached"));
+    }
+    
+    #[test]
+    fn test_derivative_cache() {
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        // Kerr in Boyer-Lindquist coordinates
+        let sigma = "(r^2 + a^2*cos(theta)^2)";
+        let g_t_phi = format!("-2*M*a*r*sin(theta)^2/{}", sigma);
+        let metric: Vec<Vec<SymbolicExpr>> = [
+            [format!("-(1 - 2*M*r/{})", sigma), "0".to_string(), "0".to_string(), g_t_phi.clone()],
+            ["0".to_string(), format!("{}/(r^2 - 2*M*r + a^2)", sigma), "0".to_string(), "0".to_string()],
+            ["0".to_string(), "0".to_string(), sigma.to_string(), "0".to_string()],
+            [g_t_phi, "0".to_string(), "0".to_string(), format!("(r^2 + a^2 + 2*M*a^2*r*sin(theta)^2/{})*sin(theta)^2", sigma)],
+        ].iter().map(|row| row.iter().map(|g| SymbolicExpr::parse(g).unwrap()).collect()).collect();
+        
+        // Second derivatives of every Kerr component, with and without the cache. Like
+        // Christoffel symbols in the Riemann tensor, the first derivatives are simplified
+        // and then each is differentiated once per upper index.
+        let first: Vec<SymbolicExpr> = metric.iter().flatten()
+            .flat_map(|g| g.gradient(&coords))
+            .map(|d| d.simplify())
+            .collect();
+        
+        let started = Instant::now();
+        let mut uncached = Vec::new();
+        for _ in 0..coords.len() {
+            for d in &first {
+                uncached.extend(d.gradient(&coords));
+            }
+        }
+        let uncached_time = started.elapsed();
+        
+        let started = Instant::now();
+        let mut cache = DerivativeCache::new();
+        let mut cached = Vec::new();
+        for _ in 0..coords.len() {
+            for d in &first {
+                cached.extend(cache.gradient(d, &coords));
+            }
+        }
+        let cached_time = started.elapsed();
+        
+        assert_eq!(cached.len(), uncached.len());
+        for (a, b) in cached.iter().zip(&uncached).take(cached.len() / coords.len()) {
+            assert!(a.approx_eq(b), "{} != {}", a, b);
+        }
+        // g_tφ = g_φt, the zero components and every repeat are only differentiated once
+        assert!(cache.len() < cached.len() / coords.len());
+        assert!(cached_time < uncached_time, "cached {:?}, uncached {:?}", cached_time, uncached_time);
+        
+        // Rearrangements of an expression share an entry
+        let mut cache = DerivativeCache::new();
+        let x = |input: &str| SymbolicExpr::parse(input).unwrap();
+        let derivative = cache.derivative(&x("a*x*b"), "x");
+        assert_eq!(cache.derivative(&x("b*(x*a)"), "x"), derivative);
+        assert_eq!(cache.len(), 1);
+        
+        // ... but different expressions are told apart exactly, not by hash alone
+        let mut cache = DerivativeCache::new();
+        assert_eq!(cache.derivative(&x("a*x - b*x"), "x"), x("a*x - b*x").derivative("x"));
+        assert_eq!(cache.derivative(&x("b*x - a*x"), "x"), x("b*x - a*x").derivative("x"));
+        assert_eq!(cache.len(), 2);
+    }
+    
+    #[test]
+    fn test_to_sympy() {
+        let sympy = |input: &str| SymbolicExpr::parse(input).unwrap().to_sympy();
+        // Schwarzschild g_rr
+        assert_eq!(sympy("1/(1 - 2*M/r)"), "1/(1 - 2*M/r)");
+        assert_eq!(sympy("r^2 * sin(theta)^2"), "r**2*sin(theta)**2");
+        assert_eq!(sympy("a / (b * c)"), "a/(b*c)");
+        assert_eq!(sympy("(x^2)^3"), "(x**2)**3");
+        assert_eq!(sympy("x^(-1)"), "x**(-1)");
+        assert_eq!(sympy("a - (b - c)"), "a - (b - c)");
+        assert_eq!(sympy("-(a + b) * ln(x)"), "-(a + b)*log(x)");
+    }
+    
+    #[test]
+    fn test_to_latex() {
+        let latex = |input: &str| SymbolicExpr::parse(input).unwrap().to_latex();
+        assert_eq!(latex("r^2"), "r^{2}");
+        assert_eq!(latex("1/(1-2*M/r)"), r"\frac{1}{1 - \frac{2 M}{r}}");
+        assert_eq!(latex("sin(theta)^2"), r"\sin^{2}\left(\theta\right)");
+        assert_eq!(latex("Omega_m + Omega_Lambda"), r"\Omega_m + \Omega_{\Lambda}");
+        assert_eq!(latex("(a + b)*(a - b)"), r"\left(a + b\right) \left(a - b\right)");
+        assert_eq!(latex("-(x^2)^3"), r"-\left(x^{2}\right)^{3}");
+        assert_eq!(latex("(1 - 2*M/r) * r^2"), r"\left(1 - \frac{2 M}{r}\right) r^{2}");
+        assert_eq!(latex("-2*x"), r"-2 x");
+        assert_eq!(latex("exp(H*t)"), r"\exp\left(H t\right)");
+    }
+
+    #[test]
+    fn test_greek_symbols() {
+        let latex = |input: &str| SymbolicExpr::parse(input).unwrap().to_latex();
+        let unicode = |input: &str| SymbolicExpr::parse(input).unwrap().to_unicode();
+
+        for (name, symbol) in [
+            ("theta", "θ"), ("phi", "φ"), ("psi", "ψ"), ("chi", "χ"), ("rho", "ρ"), ("tau", "τ"),
+            ("alpha", "α"), ("beta", "β"), ("sigma", "σ"), ("omega", "ω"), ("epsilon", "ε"),
+            ("Lambda", "Λ"), ("Omega", "Ω"), ("Phi", "Φ"), ("Gamma", "Γ"), ("Delta", "Δ"),
+        ] {
+            assert_eq!(latex(name), format!("\\{}", name));
+            assert_eq!(unicode(name), symbol);
+        }
+
+        assert_eq!(unicode("Omega_m"), "Ω_m");
+        assert_eq!(latex("Omega_m"), r"\Omega_m");
+        assert_eq!(unicode("Omega_Lambda"), "Ω_Λ");
+        assert_eq!(unicode("x_mu"), "x_μ");
+        assert_eq!(unicode("r^2 * sin(theta)^2"), "(r^2 * sin(θ)^2)");
+        assert_eq!(unicode("phi'(tau) + Lambda * g"), "(φ'(τ) + (Λ * g))");
+        assert_eq!(unicode("phi'_theta(t, theta)"), "φ'_θ(t, θ)");
+        // Only whole names are replaced
+        assert_eq!(unicode("theta2 + eta_ij + Mu"), "((theta2 + η_ij) + Mu)");
+    }
+    
+    #[test]
+    fn test_eval() {
+        let bindings = HashMap::from([("theta".to_string(), std::f64::consts::FRAC_PI_2)]);
+        let value = SymbolicExpr::parse("sin(theta)^2").unwrap().eval(&bindings).unwrap();
+        assert!((value - 1.0).abs() < 1e-12);
+
+        let lapse = SymbolicExpr::parse("1/(1-2*M/r)").unwrap();
+        let bindings = HashMap::from([("M".to_string(), 1.0), ("r".to_string(), 4.0)]);
+        assert!((lapse.eval(&bindings).unwrap() - 2.0).abs() < 1e-12);
+
+        // Division by zero, unbound variables and unknown functions are errors
+        let horizon = HashMap::from([("M".to_string(), 1.0), ("r".to_string(), 2.0)]);
+        assert!(matches!(lapse.eval(&horizon), Err(TensorError::ComputationError(_))));
+        assert!(matches!(lapse.eval(&HashMap::new()), Err(TensorError::ComputationError(_))));
+        let unknown = SymbolicExpr::parse("f(r)").unwrap();
+        assert!(matches!(unknown.eval(&bindings), Err(TensorError::ComputationError(_))));
+    }
+
+    #[test]
+    fn test_context_function_derivatives() {
+        let mut context = SymbolicContext::new();
+        context.define_function("a", "t", SymbolicExpr::parse("t^3").unwrap());
+        context.set_variable("t", SymbolicExpr::Constant(2.0));
+
+        let expr = SymbolicExpr::parse("a(t) + a'(t) + a''(t)").unwrap();
+        assert_eq!(context.apply(&expr), SymbolicExpr::Constant(8.0 + 12.0 + 12.0));
+
+        // An explicit derivative replaces the computed one
+        context.define_function("a'", "t", SymbolicExpr::parse("5*t").unwrap());
+        assert_eq!(context.apply(&SymbolicExpr::parse("a'(t)").unwrap()), SymbolicExpr::Constant(10.0));
+
+        // Variables of the context are substituted inside function bodies too
+        let mut context = SymbolicContext::new();
+        context.set_variable("H", SymbolicExpr::Constant(2.0));
+        context.define_function("a", "t", SymbolicExpr::parse("exp(H*t)").unwrap());
+        assert_expr_eq!(context.apply(&SymbolicExpr::parse("a(t)").unwrap()), SymbolicExpr::parse("exp(2*t)").unwrap());
+        assert_expr_eq!(context.apply(&SymbolicExpr::parse("a'(t)").unwrap()), SymbolicExpr::parse("2*exp(2*t)").unwrap());
+    }
+
+    #[test]
+    fn test_solve_polynomial() {
+        // r^2 - 2*M*r + Q^2, the Reissner-Nordström horizons
+        let coeffs = vec![
+            SymbolicExpr::parse("Q^2").unwrap(),
+            SymbolicExpr::parse("-2*M").unwrap(),
+            SymbolicExpr::One,
+        ];
+        let roots = solve_polynomial(&coeffs, "r").unwrap();
+        assert_eq!(roots.len(), 2);
+        assert_expr_eq!(roots[0], SymbolicExpr::parse("M + sqrt(M^2 - Q^2)").unwrap());
+        assert_expr_eq!(roots[1], SymbolicExpr::parse("M - sqrt(M^2 - Q^2)").unwrap());
+
+        // (x - 1)(x - 2)(x - 3)(x^2 + 1) has three real roots
+        let coeffs: Vec<SymbolicExpr> = [-6.0, 11.0, -12.0, 12.0, -6.0, 1.0].iter()
+            .map(|&c| SymbolicExpr::Constant(c))
+            .collect();
+        let roots = solve_polynomial(&coeffs, "x").unwrap();
+        assert_eq!(roots.len(), 3);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((root.eval(&HashMap::new()).unwrap() - expected).abs() < 1e-9);
+        }
+
+        let symbolic_cubic = vec![SymbolicExpr::One, SymbolicExpr::Zero, SymbolicExpr::Zero, SymbolicExpr::Variable("a".to_string())];
+        assert!(solve_polynomial(&symbolic_cubic, "x").is_err());
+    }
+
+    #[test]
+    fn test_simplify() {
+        let expr = SymbolicExpr::Add(
+            Box::new(SymbolicExpr::Constant(2.0)),
+            Box::new(SymbolicExpr::Constant(3.0)),
+        );
+        assert_eq!(expr.simplify(), SymbolicExpr::Constant(5.0));
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a_plus_b = SymbolicExpr::parse("a + b").unwrap();
+        assert!(a_plus_b.approx_eq(&SymbolicExpr::parse("b + a").unwrap()));
+        assert!(!a_plus_b.approx_eq(&SymbolicExpr::parse("a - b").unwrap()));
+
+        assert_expr_eq!(a_plus_b, SymbolicExpr::parse("b + a").unwrap());
+        assert_expr_eq!(SymbolicExpr::parse("sin(x)^2 + cos(x)^2").unwrap(), SymbolicExpr::One);
+    }
+
+    #[test]
+    #[should_panic(expected = "left ≈ right")]
+    fn test_assert_expr_eq_rejects() {
+        assert_expr_eq!(SymbolicExpr::parse("a + b").unwrap(), SymbolicExpr::parse("a - b").unwrap());
+    }
+
+    #[test]
+    fn test_simplify_divide() {
+        assert_eq!(SymbolicExpr::parse("(2*r)/2").unwrap().simplify(), SymbolicExpr::Variable("r".to_string()));
+        assert_eq!(SymbolicExpr::parse("(a*b)/b").unwrap().simplify(), SymbolicExpr::Variable("a".to_string()));
+        assert_eq!(SymbolicExpr::parse("sin(x)/sin(x)").unwrap().simplify(), SymbolicExpr::One);
+        assert_eq!(SymbolicExpr::parse("1/(1/(1 - x))").unwrap().simplify(), SymbolicExpr::parse("1 - x").unwrap());
+        assert_eq!(SymbolicExpr::Divide(Box::new(SymbolicExpr::One), Box::new(SymbolicExpr::Constant(-4.0))).simplify(), SymbolicExpr::Constant(-0.25));
+
+        // a/0 stays symbolic instead of becoming infinity
+        let by_zero = SymbolicExpr::Divide(Box::new(SymbolicExpr::Constant(3.0)), Box::new(SymbolicExpr::Zero));
+        assert_eq!(by_zero.simplify(), by_zero);
+        assert!(by_zero.eval(&HashMap::new()).is_err());
+        let zero_by_zero = SymbolicExpr::parse("0/0").unwrap();
+        assert_eq!(zero_by_zero.simplify(), zero_by_zero);
+        
+        // 0^-1 is 1/0, not 0
+        let zero_inverse = SymbolicExpr::parse("0^-1").unwrap();
+        assert_eq!(zero_inverse.simplify(), zero_inverse);
+        assert!(zero_inverse.eval(&HashMap::new()).is_err());
+        assert_eq!(SymbolicExpr::parse("0^2").unwrap().simplify(), SymbolicExpr::Zero);
+    }
+
+    #[test]
+    fn test_simplify_roots() {
+        let x = SymbolicExpr::Variable("x".to_string());
+        let abs_x = SymbolicExpr::Function("abs".to_string(), vec![x.clone()]);
+
+        assert_eq!(SymbolicExpr::parse("sqrt(x)^2").unwrap().simplify(), x);
+        assert_eq!(SymbolicExpr::parse("cbrt(x)^3").unwrap().simplify(), x);
+        assert_eq!(SymbolicExpr::parse("sqrt(x^2)").unwrap().simplify(), abs_x);
+        assert_eq!(
+            SymbolicExpr::parse("sqrt(a) * sqrt(b)").unwrap().simplify(),
+            SymbolicExpr::parse("sqrt(a * b)").unwrap(),
+        );
+
+        assert_eq!(SymbolicExpr::parse("sqrt(a) * sqrt(a)").unwrap().simplify(), SymbolicExpr::parse("a").unwrap());
+
+        // Perfect-square constants are taken exactly; others stay symbolic
+        assert_eq!(SymbolicExpr::parse("sqrt(9)").unwrap().simplify(), SymbolicExpr::Constant(3.0));
+        assert_eq!(SymbolicExpr::parse("sqrt(1)").unwrap().simplify(), SymbolicExpr::One);
+        assert_eq!(SymbolicExpr::parse("sqrt(2)").unwrap().simplify().to_string(), "sqrt(2)");
+
+        // Half powers are square roots, whether parsed or built directly
+        let sqrt_x = SymbolicExpr::Function("sqrt".to_string(), vec![x.clone()]);
+        assert_eq!(SymbolicExpr::parse("x^(1/2)").unwrap(), sqrt_x);
+        assert_eq!(SymbolicExpr::parse("x^0.5").unwrap(), sqrt_x);
+        let half_power = SymbolicExpr::Power(
+            Box::new(x.clone()),
+            Box::new(SymbolicExpr::Divide(Box::new(SymbolicExpr::One), Box::new(SymbolicExpr::Constant(2.0)))),
+        );
+        assert_eq!(half_power.simplify(), sqrt_x);
+
+        // Arguments are simplified too
+        assert_eq!(SymbolicExpr::parse("sqrt(r^2 * 1)").unwrap().simplify().to_string(), "abs(r)");
+    }
+
+    #[test]
+    fn test_simplify_powers() {
+        let simplified = |src: &str| SymbolicExpr::parse(src).unwrap().simplify();
+        let parsed = |src: &str| SymbolicExpr::parse(src).unwrap();
+
+        assert_eq!(simplified("(r^2)^3"), parsed("r^6"));
+        assert_eq!(simplified("r^2 * r^3"), parsed("r^5"));
+        assert_eq!(simplified("r^5 / r^2"), parsed("r^3"));
+        assert_eq!(simplified("r * r^2"), parsed("r^3"));
+
+        // Symbolic exponents combine when the bases match
+        assert_eq!(simplified("r^a * r^b"), parsed("r^(a + b)"));
+        assert_eq!(simplified("r^a / r^b"), parsed("r^(a - b)"));
+        assert_eq!(simplified("r^a * s^b"), parsed("r^a * s^b"));
+
+        // (x^2)^(1/2) is |x|, not x, so only integer outer exponents are merged
+        assert_eq!(simplified("(x^2)^(1/2)").to_string(), "abs(x)");
+        assert_eq!(simplified("(x^a)^b"), parsed("(x^a)^b"));
+    }
+
+    #[test]
+    fn test_simplify_with() {
+        let defaults = SimplifyOptions::default();
+        let simplified = |src: &str, opts: SimplifyOptions| SymbolicExpr::parse(src).unwrap().simplify_with(opts).to_string();
+
+        let expr = SymbolicExpr::parse("(x + 1)^2 - x^2 - 2*x").unwrap();
+        assert_eq!(expr.simplify_with(defaults), expr.simplify());
+
+        // Expansion
+        assert_eq!(simplified("(x + 1)^2 - x^2 - 2*x", defaults), "(((x + 1)^2 - x^2) - (2 * x))");
+        assert_eq!(simplified("(x + 1)^2 - x^2 - 2*x", SimplifyOptions { expand: true, ..defaults }), "1");
+
+        // Trig identities, with and without expansion
+        let no_trig = SimplifyOptions { trig_identities: false, ..defaults };
+        assert_eq!(simplified("r * sin(t)^2 + r * cos(t)^2", defaults), "r");
+        assert_eq!(simplified("r * sin(t)^2 + r * cos(t)^2", no_trig), "((r * sin(t)^2) + (r * cos(t)^2))");
+        assert_eq!(simplified("(sin(t)^2 + cos(t)^2) * r", SimplifyOptions { expand: true, ..defaults }), "r");
+        assert_eq!(
+            simplified("(sin(t)^2 + cos(t)^2) * r", SimplifyOptions { expand: true, ..no_trig }),
+            "((sin(t)^2 + cos(t)^2) * r)"
+        );
+
+        // Factor cancellation
+        let no_cancel = SimplifyOptions { cancel_factors: false, ..defaults };
+        assert_eq!(simplified("(a*b)/(a*c)", defaults), "(b / c)");
+        assert_eq!(simplified("(a*b)/(a*c)", no_cancel), "((a * b) / (a * c))");
+        assert_eq!(simplified("x^a / x^b", no_cancel), "(x^a / x^b)");
+
+        // Iteration count
+        assert_eq!(simplified("x * (y / x) - y", SimplifyOptions { max_iterations: 0, ..defaults }), "((x * (y / x)) - y)");
+        assert_eq!(simplified("x * (y / x) - y", SimplifyOptions { max_iterations: 1, ..defaults }), "(((x * y) / x) - y)");
+        assert_eq!(simplified("x * (y / x) - y", defaults), "0");
+    }
+
+    #[test]
+    fn test_derivative() {
+        let expr = SymbolicExpr::Variable("x".to_string());
+        assert_eq!(expr.derivative("x"), SymbolicExpr::One);
+        assert_eq!(expr.derivative("y"), SymbolicExpr::Zero);
+
+        // Unknown functions differentiate to primed functions via the chain rule
+        let mass = SymbolicExpr::parse("M(v)").unwrap();
+        assert_eq!(mass.derivative("v").simplify(), SymbolicExpr::parse("M'(v)").unwrap());
+        assert_eq!(mass.derivative("r").simplify(), SymbolicExpr::Zero);
+        assert!(SymbolicExpr::parse("a(t)^2").unwrap().derivative("t").to_string().contains("a'(t)"));
+        crate::assert_expr_eq!(
+            SymbolicExpr::parse("a(t)^2").unwrap().derivative("t").simplify(),
+            SymbolicExpr::parse("2*a(t)*a'(t)").unwrap()
+        );
+        assert_eq!(
+            SymbolicExpr::parse("a(t)").unwrap().derivative("t").derivative("t").simplify(),
+            SymbolicExpr::parse("a''(t)").unwrap()
+        );
+        assert_eq!(
+            SymbolicExpr::parse("exp(a(2*t))").unwrap().derivative("t").expand(),
+            SymbolicExpr::parse("2*a'(2*t)*exp(a(2*t))").unwrap().expand()
+        );
+
+        // Several arguments: primed partial derivatives indexed by the argument
+        let field = SymbolicExpr::parse("phi(t, r)").unwrap();
+        assert_eq!(field.derivative("r").simplify(), SymbolicExpr::parse("phi'_r(t, r)").unwrap());
+        assert_eq!(field.derivative("t").derivative("r").simplify(), SymbolicExpr::parse("phi'_t_r(t, r)").unwrap());
+        assert_eq!(field.derivative("r").derivative("t").simplify(), field.derivative("t").derivative("r").simplify());
+        assert_eq!(field.derivative("r").derivative("r").simplify(), SymbolicExpr::parse("phi'_r_r(t, r)").unwrap());
+        let mixed = |f: &SymbolicExpr, a: &str, b: &str| SymbolicExpr::Subtract(
+            Box::new(f.derivative(a).derivative(b)),
+            Box::new(f.derivative(b).derivative(a)),
+        );
+        assert!(mixed(&field, "t", "r").simplify().is_zero());
+        assert!(mixed(&SymbolicExpr::parse("g(t, r^2) * h(r, t)").unwrap(), "t", "r").expand().is_zero());
+        assert_eq!(field.derivative("x").simplify(), SymbolicExpr::Zero);
+        assert_eq!(
+            SymbolicExpr::parse("f(t, 2*t)").unwrap().derivative("t").expand(),
+            SymbolicExpr::parse("f'_t(t, 2*t) + 2*f'_2(t, 2*t)").unwrap().expand()
+        );
+        
+        // ... which can't be mistaken for a function that merely has a subscript
+        let subscripted = SymbolicExpr::parse("phi_r(t, r)").unwrap();
+        assert_ne!(subscripted.derivative("r").simplify(), field.derivative("r").simplify());
+    }
+
+    #[test]
+    fn test_derivative_n() {
+        let r_cubed = SymbolicExpr::parse("r^3").unwrap();
+        assert_eq!(r_cubed.derivative_n("r", 0), r_cubed);
+        assert_eq!(r_cubed.derivative_n("r", 2).to_string(), "(6 * r)");
+        assert_eq!(r_cubed.derivative_n("r", 4), SymbolicExpr::Zero);
+
+        let sine = SymbolicExpr::parse("sin(x)").unwrap();
+        crate::assert_expr_eq!(sine.derivative_n("x", 2), SymbolicExpr::parse("-sin(x)").unwrap());
+        crate::assert_expr_eq!(sine.derivative_n("x", 4), sine);
+        assert_eq!(SymbolicExpr::parse("a(t)").unwrap().derivative_n("t", 2), SymbolicExpr::parse("a''(t)").unwrap());
+    }
+
+    #[test]
+    fn test_series() {
+        let geometric = SymbolicExpr::parse("1/(1 - x)").unwrap();
+        let expansion = geometric.series("x", 0.0, 2).unwrap();
+        assert_eq!(expansion, SymbolicExpr::parse("1 + x + x^2").unwrap(), "{}", expansion);
+        
+        // Around a nonzero point: ln(x) = (x - 1) - (x - 1)^2 / 2 + ...
+        let log = SymbolicExpr::parse("ln(x)").unwrap().series("x", 1.0, 2).unwrap();
+        crate::assert_expr_eq!(log, SymbolicExpr::parse("(x - 1) - (x - 1)^2 / 2").unwrap());
+        
+        // Weak field: the lapse sqrt(1 - 2M/r) with u = 1/r, to first order in u
+        let lapse = SymbolicExpr::parse("sqrt(1 - 2*M*u)").unwrap().series("u", 0.0, 1).unwrap();
+        crate::assert_expr_eq!(lapse, SymbolicExpr::parse("1 - M*u").unwrap());
+        
+        assert!(SymbolicExpr::parse("1/x").unwrap().series("x", 0.0, 1).is_err());
+    }
+
+    #[test]
+    fn test_gradient_and_mixed_partial() {
+        let vars: Vec<String> = ["x", "y"].iter().map(|s| s.to_string()).collect();
+        let gradient = SymbolicExpr::parse("x^2 + y^2").unwrap().gradient(&vars);
+        assert_eq!(gradient.len(), 2);
+        assert_eq!(gradient[0].to_string(), "(2 * x)");
+        assert_eq!(gradient[1].to_string(), "(2 * y)");
+
+        assert_eq!(SymbolicExpr::parse("x*y").unwrap().mixed_partial(&vars), SymbolicExpr::One);
+        crate::assert_expr_eq!(
+            SymbolicExpr::parse("x^2 * sin(y)").unwrap().mixed_partial(&vars),
+            SymbolicExpr::parse("2*x*cos(y)").unwrap()
+        );
+        assert_eq!(SymbolicExpr::parse("x*y").unwrap().mixed_partial(&[]), SymbolicExpr::parse("x*y").unwrap());
+    }
+
+    #[test]
+    fn test_derivative_builtin_functions() {
+        let derivative = |input: &str| SymbolicExpr::parse(input).unwrap().derivative("t").simplify();
+        let check = |input: &str, expected: &str| {
+            crate::assert_expr_eq!(derivative(input), SymbolicExpr::parse(expected).unwrap());
+        };
+
+        assert_eq!(derivative("exp(H*t)").to_string(), "(exp((H * t)) * H)");
+        check("sin(2*t)", "2*cos(2*t)");
+        check("cos(t^2)", "-2*t*sin(t^2)");
+        check("tan(t)", "1 / cos(t)^2");
+        check("ln(t^2)", "2 / t");
+        check("log(3*t)", "1 / t");
+        check("sqrt(t)", "1 / (2*sqrt(t))");
+        check("cbrt(t)", "1 / (3*t^(2/3))");
+        check("sinh(k*t)", "k*cosh(k*t)");
+        check("cosh(t)", "sinh(t)");
+        check("tanh(t)", "1 - tanh(t)^2");
+        check("abs(t - 1)", "(t - 1) / abs(t - 1)");
+        assert_eq!(derivative("exp(H*r)"), SymbolicExpr::Zero);
+    }
+
+    #[test]
+    fn test_derivative_variable_exponent() {
+        let derivative = |input: &str, var: &str| SymbolicExpr::parse(input).unwrap().derivative(var).simplify();
+
+        // d/dx x^x = x^x (ln(x) + 1)
+        crate::assert_expr_eq!(derivative("x^x", "x"), SymbolicExpr::parse("x^x * (ln(x) + 1)").unwrap());
+
+        // A free exponent differentiates like a constant one, but also depends on n
+        crate::assert_expr_eq!(derivative("r^n", "r"), SymbolicExpr::parse("n * r^(n - 1)").unwrap());
+        crate::assert_expr_eq!(derivative("r^n", "n"), SymbolicExpr::parse("r^n * ln(r)").unwrap());
+        crate::assert_expr_eq!(derivative("2^t", "t"), SymbolicExpr::parse("2^t * ln(2)").unwrap());
+
+        // Numeric exponents keep the plain power rule
+        assert_eq!(derivative("x^2", "x").to_string(), "(2 * x)");
+        assert!(!derivative("x^3", "x").to_string().contains("ln"));
     }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use crate::symbolic::SymbolicExpr;
+use crate::symbolic::{solve_polynomial, CseState, DerivativeCache, SymbolicContext, SymbolicExpr};
 use crate::TensorError;
 
 pub type MetricTensor = Vec<Vec<SymbolicExpr>>;
@@ -12,6 +12,53 @@ pub struct TensorComponent {
     pub expression: String,
 }
 
+/// A `TensorComponent` that also carries its parsed expression, so JSON consumers get
+/// the `SymbolicExpr` tree under `expression_tree` instead of re-parsing the string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TensorComponentTyped {
+    pub indices: Vec<usize>,
+    pub expression: String,
+    pub expression_tree: SymbolicExpr,
+}
+
+impl From<TensorComponentTyped> for TensorComponent {
+    fn from(component: TensorComponentTyped) -> Self {
+        TensorComponent { indices: component.indices, expression: component.expression }
+    }
+}
+
+impl TensorComponent {
+    /// Parses the expression string into a `TensorComponentTyped`.
+    pub fn typed(&self) -> Result<TensorComponentTyped, TensorError> {
+        Ok(TensorComponentTyped {
+            indices: self.indices.clone(),
+            expression: self.expression.clone(),
+            expression_tree: SymbolicExpr::parse(&self.expression)?,
+        })
+    }
+
+    /// Labels the component in index notation, e.g. `R^{0}_{101} = ...` for a Riemann
+    /// component with `upper = [true, false, false, false]`. Indices past the end of
+    /// `upper` are lower.
+    pub fn format_indexed(&self, tensor_name: &str, upper: &[bool]) -> String {
+        let is_upper = |position: usize| upper.get(position).copied().unwrap_or(false);
+        let separator = if self.indices.iter().any(|&index| index > 9) { "," } else { "" };
+        
+        let mut label = tensor_name.to_string();
+        let mut position = 0;
+        while position < self.indices.len() {
+            let group_upper = is_upper(position);
+            let mut group = Vec::new();
+            while position < self.indices.len() && is_upper(position) == group_upper {
+                group.push(self.indices[position].to_string());
+                position += 1;
+            }
+            label.push_str(&format!("{}{{{}}}", if group_upper { "^" } else { "_" }, group.join(separator)));
+        }
+        format!("{} = {}", label, self.expression)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChristoffelResult {
     pub symbols: Vec<TensorComponent>,
@@ -24,6 +71,116 @@ pub struct RiemannResult {
     pub dimension: usize,
 }
 
+/// Components that differ between two tensors, as reported by `diff_tensors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorDiff {
+    pub differences: Vec<ComponentDiff>,
+}
+
+impl TensorDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// A single differing component. Components absent from a result are zero.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentDiff {
+    pub indices: Vec<usize>,
+    pub before: String,
+    pub after: String,
+    /// `after - before`, simplified
+    pub difference: String,
+}
+
+/// Tensor components with repeated sub-expressions replaced by named bindings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommonSubexpressions {
+    /// Shared sub-expressions in dependency order; each may refer to earlier names.
+    pub bindings: Vec<SubexpressionBinding>,
+    pub components: Vec<TensorComponent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubexpressionBinding {
+    pub name: String,
+    pub expression: String,
+}
+
+impl CommonSubexpressions {
+    /// A context defining every binding, for substituting them back into components.
+    pub fn context(&self) -> Result<SymbolicContext, TensorError> {
+        let mut context = SymbolicContext::new();
+        for binding in &self.bindings {
+            context.set_variable(&binding.name, SymbolicExpr::parse(&binding.expression)?);
+        }
+        Ok(context)
+    }
+
+    /// Substitutes the bindings back in, recovering the original components.
+    pub fn expand(&self) -> Result<Vec<TensorComponent>, TensorError> {
+        let context = self.context()?;
+        self.components.iter()
+            .map(|component| Ok(TensorComponent {
+                indices: component.indices.clone(),
+                expression: context.substitute(&SymbolicExpr::parse(&component.expression)?).to_string(),
+            }))
+            .collect()
+    }
+}
+
+/// Options controlling the curvature computations.
+#[derive(Debug, Clone, Default)]
+pub struct CurvatureOptions {
+    /// Skip the metric symmetry check, for experimenting with non-symmetric connections.
+    pub allow_asymmetric: bool,
+    /// Emit every component, including those that simplify to zero, instead of only
+    /// the non-zero ones.
+    pub include_zeros: bool,
+    /// Limits that abort pathological computations instead of letting them run away.
+    pub budget: ComputationBudget,
+}
+
+/// Size limits for the curvature computations. Exceeding either one returns a
+/// `ComputationError` rather than hanging or exhausting memory; the defaults are far
+/// above anything a hand-written 4D metric needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputationBudget {
+    /// Largest expression tree, in nodes, allowed for any intermediate expression.
+    pub max_nodes: usize,
+    /// Largest number of components a single tensor may have.
+    pub max_components: usize,
+}
+
+impl Default for ComputationBudget {
+    fn default() -> Self {
+        ComputationBudget { max_nodes: 200_000, max_components: 100_000 }
+    }
+}
+
+impl ComputationBudget {
+    fn check_components(&self, count: usize) -> Result<(), TensorError> {
+        if count > self.max_components {
+            return Err(TensorError::ComputationError(format!(
+                "Computation budget exceeded: {} components (limit {})", count, self.max_components
+            )));
+        }
+        Ok(())
+    }
+    
+    fn check_expr(&self, expr: &SymbolicExpr) -> Result<(), TensorError> {
+        let nodes = expr.node_count();
+        if nodes > self.max_nodes {
+            return Err(TensorError::ComputationError(format!(
+                "Computation budget exceeded: expression has {} nodes (limit {})", nodes, self.max_nodes
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a square matrix of metric entries. Entries are parsed strictly, so an empty
+/// or malformed entry such as `r^2 *` is a `ParseError` naming its row and column.
 pub fn parse_metric_tensor(metric_strings: Vec<Vec<String>>, _coords: &[String]) -> Result<MetricTensor, TensorError> {
     let n = metric_strings.len();
     
@@ -36,11 +193,12 @@ pub fn parse_metric_tensor(metric_strings: Vec<Vec<String>>, _coords: &[String])
     
     let mut metric = Vec::with_capacity(n);
     
-    for row in metric_strings {
+    for (i, row) in metric_strings.into_iter().enumerate() {
         let mut parsed_row = Vec::with_capacity(n);
-        for expr_str in row {
-            let expr = SymbolicExpr::parse(&expr_str)
-                .map_err(|e| TensorError::InvalidMetric(format!("Failed to parse expression '{}': {}", expr_str, e)))?;
+        for (j, expr_str) in row.into_iter().enumerate() {
+            let expr = SymbolicExpr::parse_strict(&expr_str).map_err(|e| TensorError::ParseError(format!(
+                "Metric entry ({}, {}) '{}': {}", i, j, expr_str, e
+            )))?;
             parsed_row.push(expr);
         }
         metric.push(parsed_row);
@@ -49,12 +207,180 @@ pub fn parse_metric_tensor(metric_strings: Vec<Vec<String>>, _coords: &[String])
     Ok(metric)
 }
 
-pub fn calculate_christoffel_symbols(metric: &MetricTensor, coords: &[String]) -> Result<ChristoffelResult, TensorError> {
+/// Parses a diagonal metric from just its diagonal entries, with zeros elsewhere.
+pub fn parse_diagonal_metric(entries: Vec<String>, coords: &[String]) -> Result<MetricTensor, TensorError> {
+    if entries.len() != coords.len() {
+        return Err(TensorError::InvalidMetric(format!(
+            "Diagonal metric has {} entries but {} coordinates were given", entries.len(), coords.len()
+        )));
+    }
+    
+    let n = entries.len();
+    let rows = entries.into_iter().enumerate()
+        .map(|(i, entry)| {
+            let mut row = vec!["0".to_string(); n];
+            row[i] = entry;
+            row
+        })
+        .collect();
+    parse_metric_tensor(rows, coords)
+}
+
+/// Parses a metric written as a SymPy `Matrix([[...], [...]])` or a Mathematica
+/// `{{...}, {...}}` list. Entries may use `**` for powers and Mathematica's
+/// `Sin[theta]`-style function calls.
+pub fn parse_metric_from_sympy(matrix_src: &str, coords: &[String]) -> Result<MetricTensor, TensorError> {
+    let src = matrix_src.trim();
+    let src = src.strip_prefix("Matrix(")
+        .and_then(|inner| inner.strip_suffix(')'))
+        .unwrap_or(src);
+    let invalid = || TensorError::ParseError(format!("Expected a nested list of metric entries, got '{}'", matrix_src));
+    
+    let mathematica_names = regex::Regex::new(r"\b(Sin|Cos|Tan|Exp|Log|Sqrt|Sinh|Cosh|Tanh|Abs|Pi)\b")
+        .expect("valid regex");
+    let mut rows = Vec::new();
+    for row in split_top_level(strip_list_brackets(src).ok_or_else(invalid)?) {
+        let entries = split_top_level(strip_list_brackets(row).ok_or_else(invalid)?);
+        rows.push(entries.into_iter()
+            .map(|entry| {
+                let entry = entry.replace("**", "^").replace('[', "(").replace(']', ")");
+                mathematica_names.replace_all(&entry, |caps: &regex::Captures| caps[1].to_lowercase()).into_owned()
+            })
+            .collect());
+    }
+    parse_metric_tensor(rows, coords)
+}
+
+/// The contents of a `[...]` or `{...}` list, or `None` if `src` isn't one.
+fn strip_list_brackets(src: &str) -> Option<&str> {
+    let src = src.trim();
+    src.strip_prefix('[').and_then(|inner| inner.strip_suffix(']'))
+        .or_else(|| src.strip_prefix('{').and_then(|inner| inner.strip_suffix('}')))
+}
+
+/// Splits on commas that are not nested inside brackets or parentheses.
+fn split_top_level(src: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in src.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(src[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !src[start..].trim().is_empty() {
+        parts.push(src[start..].trim());
+    }
+    parts
+}
+
+/// Returns an error if `g_μν` and `g_νμ` differ for any pair of indices.
+#[allow(clippy::needless_range_loop)]
+pub fn check_metric_symmetry(metric: &MetricTensor) -> Result<(), TensorError> {
     let n = metric.len();
-    let mut symbols = Vec::new();
+
+    for mu in 0..n {
+        for nu in (mu + 1)..n {
+            if !metric[mu][nu].is_equivalent(&metric[nu][mu]) {
+                return Err(TensorError::InvalidMetric(format!(
+                    "Metric tensor must be symmetric: g[{}][{}] = {} but g[{}][{}] = {}",
+                    mu, nu, metric[mu][nu], nu, mu, metric[nu][mu]
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_metric(metric: &MetricTensor, options: &CurvatureOptions) -> Result<(), TensorError> {
+    if !options.allow_asymmetric {
+        check_metric_symmetry(metric)?;
+    }
+    Ok(())
+}
+
+pub fn calculate_christoffel_symbols(metric: &MetricTensor, coords: &[String]) -> Result<ChristoffelResult, TensorError> {
+    calculate_christoffel_symbols_with_options(metric, coords, &CurvatureOptions::default())
+}
+
+pub fn calculate_christoffel_symbols_with_options(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<ChristoffelResult, TensorError> {
+    let christoffel = christoffel_tensor(metric, coords, options)?;
     
-    // Calculate metric inverse (simplified - in real implementation would use proper matrix inversion)
+    Ok(ChristoffelResult {
+        symbols: rank3_components(&christoffel, options.include_zeros),
+        dimension: metric.len(),
+    })
+}
+
+/// Christoffel symbols as a dense `[μ][α][β]` array, zeros included.
+pub fn calculate_christoffel_symbols_full(metric: &MetricTensor, coords: &[String]) -> Result<ChristoffelSymbols, TensorError> {
+    christoffel_tensor(metric, coords, &CurvatureOptions::default())
+}
+
+fn christoffel_tensor(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<ChristoffelSymbols, TensorError> {
+    validate_metric(metric, options)?;
+    let budget = &options.budget;
+    budget.check_components(metric.len().pow(3))?;
+    for g in metric.iter().flatten() {
+        budget.check_expr(g)?;
+    }
     let metric_inv = calculate_metric_inverse(metric)?;
+    christoffel_from_inverse(metric, &metric_inv, coords, budget)
+}
+
+/// Christoffel symbols using a caller-supplied inverse metric, e.g. one known in
+/// closed form, instead of inverting the metric symbolically.
+pub fn calculate_christoffel_symbols_with_inverse(
+    metric: &MetricTensor,
+    inverse: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<ChristoffelResult, TensorError> {
+    validate_metric(metric, options)?;
+    let n = metric.len();
+    if inverse.len() != n || inverse.iter().any(|row| row.len() != n) || coords.len() != n {
+        return Err(TensorError::InvalidMetric(format!(
+            "Inverse metric and coordinates must match the {}D metric", n
+        )));
+    }
+    
+    options.budget.check_components(n.pow(3))?;
+    let christoffel = christoffel_from_inverse(metric, inverse, coords, &options.budget)?;
+    Ok(ChristoffelResult {
+        symbols: rank3_components(&christoffel, options.include_zeros),
+        dimension: n,
+    })
+}
+
+fn christoffel_from_inverse(
+    metric: &MetricTensor,
+    metric_inv: &MetricTensor,
+    coords: &[String],
+    budget: &ComputationBudget,
+) -> Result<ChristoffelSymbols, TensorError> {
+    let n = metric.len();
+    let mut symbols = vec![vec![vec![SymbolicExpr::Zero; n]; n]; n];
+    
+    // ∂g_ab/∂x^c, indexed [a][b][c]; each is needed for many symbols
+    let mut cache = DerivativeCache::new();
+    let metric_derivatives: ChristoffelSymbols = metric.iter()
+        .map(|row| row.iter().map(|g| cache.gradient(g, coords)).collect())
+        .collect();
     
     // Calculate Christoffel symbols: Γ^μ_αβ = (1/2) * g^μν * (∂g_νβ/∂x^α + ∂g_να/∂x^β - ∂g_αβ/∂x^ν)
     for mu in 0..n {
@@ -64,13 +390,13 @@ pub fn calculate_christoffel_symbols(metric: &MetricTensor, coords: &[String]) -
                 
                 for nu in 0..n {
                     // ∂g_νβ/∂x^α
-                    let d_g_nu_beta_d_alpha = metric[nu][beta].derivative(&coords[alpha]);
+                    let d_g_nu_beta_d_alpha = metric_derivatives[nu][beta][alpha].clone();
                     
                     // ∂g_να/∂x^β  
-                    let d_g_nu_alpha_d_beta = metric[nu][alpha].derivative(&coords[beta]);
+                    let d_g_nu_alpha_d_beta = metric_derivatives[nu][alpha][beta].clone();
                     
                     // ∂g_αβ/∂x^ν
-                    let d_g_alpha_beta_d_nu = metric[alpha][beta].derivative(&coords[nu]);
+                    let d_g_alpha_beta_d_nu = metric_derivatives[alpha][beta][nu].clone();
                     
                     // (∂g_νβ/∂x^α + ∂g_να/∂x^β - ∂g_αβ/∂x^ν)
                     let sum = SymbolicExpr::Subtract(
@@ -99,104 +425,305 @@ pub fn calculate_christoffel_symbols(metric: &MetricTensor, coords: &[String]) -
                     Box::new(christoffel_expr),
                 );
                 
-                let simplified = christoffel_expr.simplify();
-                
-                // Only include non-zero components
-                if !simplified.is_zero() {
-                    symbols.push(TensorComponent {
-                        indices: vec![mu, alpha, beta],
-                        expression: simplified.to_string(),
-                    });
-                }
+                budget.check_expr(&christoffel_expr)?;
+                let symbol = christoffel_expr.simplify_expanded();
+                budget.check_expr(&symbol)?;
+                symbols[mu][alpha][beta] = symbol;
             }
         }
     }
     
-    Ok(ChristoffelResult {
-        symbols,
-        dimension: n,
-    })
+    Ok(symbols)
 }
 
 pub fn calculate_riemann_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
-    let n = metric.len();
-    let mut components = Vec::new();
+    calculate_riemann_tensor_with_options(metric, coords, &CurvatureOptions::default())
+}
+
+pub fn calculate_riemann_tensor_with_options(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<RiemannResult, TensorError> {
+    let riemann = riemann_tensor(metric, coords, options)?;
+    
+    Ok(RiemannResult {
+        components: rank4_components(&riemann, options.include_zeros),
+        dimension: metric.len(),
+    })
+}
+
+/// Riemann tensor `R^ρ_σμν` as a dense `[ρ][σ][μ][ν]` array, zeros included.
+pub fn calculate_riemann_tensor_full(metric: &MetricTensor, coords: &[String]) -> Result<RiemannTensor, TensorError> {
+    riemann_tensor(metric, coords, &CurvatureOptions::default())
+}
+
+fn riemann_tensor(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<RiemannTensor, TensorError> {
+    // Checked up front so that an oversized metric is rejected before any of the
+    // Christoffel work, including the inverse
+    options.budget.check_components(metric.len().pow(4))?;
+    let christoffel = christoffel_tensor(metric, coords, options)?;
+    riemann_from_christoffel(&christoffel, coords, is_diagonal(metric), &options.budget)
+}
+
+/// Nonzero Riemann components `R^ρ_σμν`, in the same order as
+/// `calculate_riemann_tensor`, computed one `ρ` slice at a time as the iterator is
+/// advanced. Stopping early skips the remaining slices. A metric that can't be
+/// processed yields a single error.
+pub fn riemann_components_iter<'a>(
+    metric: &'a MetricTensor,
+    coords: &'a [String],
+    options: &'a CurvatureOptions,
+) -> impl Iterator<Item = Result<TensorComponent, TensorError>> + 'a {
+    RiemannComponents { metric, coords, options, state: None, rho: 0, pending: Vec::new().into_iter(), failed: false }
+}
+
+struct RiemannComponents<'a> {
+    metric: &'a MetricTensor,
+    coords: &'a [String],
+    options: &'a CurvatureOptions,
+    /// Christoffel symbols and their gradients, computed on the first call to `next`
+    state: Option<(ChristoffelSymbols, RiemannTensor)>,
+    rho: usize,
+    pending: std::vec::IntoIter<TensorComponent>,
+    failed: bool,
+}
+
+impl Iterator for RiemannComponents<'_> {
+    type Item = Result<TensorComponent, TensorError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(component) = self.pending.next() {
+                return Some(Ok(component));
+            }
+            if self.failed || self.rho >= self.metric.len() {
+                return None;
+            }
+            
+            if self.state.is_none() {
+                if self.coords.len() != self.metric.len() {
+                    self.failed = true;
+                    return Some(Err(TensorError::InvalidMetric(format!(
+                        "Metric is {}D but {} coordinates were given", self.metric.len(), self.coords.len()
+                    ))));
+                }
+                let christoffel = self.options.budget.check_components(self.metric.len().pow(4))
+                    .and_then(|()| christoffel_tensor(self.metric, self.coords, self.options));
+                match christoffel {
+                    Ok(christoffel) => {
+                        let derivatives = christoffel_gradients(&christoffel, self.coords);
+                        self.state = Some((christoffel, derivatives));
+                    }
+                    Err(e) => {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            
+            let (christoffel, derivatives) = self.state.as_ref().expect("state was initialized above");
+            let budget = &self.options.budget;
+            let slice = match riemann_slice(self.rho, christoffel, derivatives, is_diagonal(self.metric), budget) {
+                Ok(slice) => slice,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            };
+            let rho = self.rho;
+            self.pending = rank3_components(&slice, self.options.include_zeros)
+                .into_iter()
+                .map(|mut component| {
+                    component.indices.insert(0, rho);
+                    component
+                })
+                .collect::<Vec<_>>()
+                .into_iter();
+            self.rho += 1;
+        }
+    }
+}
+
+/// Riemann tensor from already computed Christoffel symbols (as returned by
+/// `calculate_christoffel_symbols_full`), so that several curvature quantities of
+/// one metric can share a single Christoffel computation. `budget` plays the same
+/// role as in `CurvatureOptions`.
+pub fn calculate_riemann_tensor_with_christoffel(
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+    budget: &ComputationBudget,
+) -> Result<RiemannTensor, TensorError> {
+    riemann_from_christoffel(christoffel, coords, false, budget)
+}
+
+/// Computes only the independent components: `R^ρ_σμν = -R^ρ_σνμ` fills in `μ > ν`,
+/// and for a diagonal metric `R^ρ_ρμν = g^ρρ R_ρρμν` vanishes by antisymmetry of
+/// `R_ρσμν` in its first pair, so those blocks are skipped entirely.
+fn riemann_from_christoffel(
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+    diagonal_metric: bool,
+    budget: &ComputationBudget,
+) -> Result<RiemannTensor, TensorError> {
+    let n = christoffel.len();
+    if coords.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "Christoffel symbols have dimension {} but {} coordinates were given", n, coords.len()
+        )));
+    }
+    budget.check_components(n.pow(4))?;
+    let christoffel_derivatives = christoffel_gradients(christoffel, coords);
+    for derivative in christoffel_derivatives.iter().flatten().flatten().flatten() {
+        budget.check_expr(derivative)?;
+    }
+    let slice = |rho| riemann_slice(rho, christoffel, &christoffel_derivatives, diagonal_metric, budget);
+    
+    // Each ρ slice is independent; collecting by index keeps the output order fixed
+    #[cfg(feature = "rayon")]
+    let riemann = {
+        use rayon::prelude::*;
+        (0..n).into_par_iter().map(slice).collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let riemann = (0..n).map(slice).collect();
     
-    // First calculate Christoffel symbols
-    let christoffel_result = calculate_christoffel_symbols(metric, coords)?;
-    let christoffel = symbols_to_tensor(&christoffel_result, n);
+    riemann
+}
+
+/// `∂Γ^ρ_σν/∂x^μ`, indexed `[ρ][σ][ν][μ]`.
+fn christoffel_gradients(christoffel: &ChristoffelSymbols, coords: &[String]) -> RiemannTensor {
+    // Γ^ρ_σν = Γ^ρ_νσ, and many symbols share a form, so most derivatives repeat
+    let mut cache = DerivativeCache::new();
+    christoffel.iter()
+        .map(|matrix| matrix.iter()
+            .map(|row| row.iter().map(|symbol| cache.gradient(symbol, coords)).collect())
+            .collect())
+        .collect()
+}
+
+/// The `R^ρ_σμν` components for one value of `ρ`, indexed `[σ][μ][ν]`.
+#[allow(clippy::needless_range_loop)]
+fn riemann_slice(
+    rho: usize,
+    christoffel: &ChristoffelSymbols,
+    christoffel_derivatives: &RiemannTensor,
+    diagonal_metric: bool,
+    budget: &ComputationBudget,
+) -> Result<ChristoffelSymbols, TensorError> {
+    let n = christoffel.len();
+    let mut slice = vec![vec![vec![SymbolicExpr::Zero; n]; n]; n];
     
     // Calculate Riemann tensor: R^ρ_σμν = ∂Γ^ρ_σν/∂x^μ - ∂Γ^ρ_σμ/∂x^ν + Γ^ρ_λμ*Γ^λ_σν - Γ^ρ_λν*Γ^λ_σμ
-    for rho in 0..n {
-        for sigma in 0..n {
-            for mu in 0..n {
-                for nu in 0..n {
-                    let mut riemann_expr = SymbolicExpr::Zero;
-                    
-                    // ∂Γ^ρ_σν/∂x^μ
-                    let d_christoffel_rho_sigma_nu_d_mu = christoffel[rho][sigma][nu].derivative(&coords[mu]);
+    // It is antisymmetric in μν, so only μ < ν is computed directly.
+    for sigma in 0..n {
+        if diagonal_metric && sigma == rho {
+            continue;
+        }
+        for mu in 0..n {
+            for nu in (mu + 1)..n {
+                let mut riemann_expr = SymbolicExpr::Zero;
+                
+                // ∂Γ^ρ_σν/∂x^μ
+                let d_christoffel_rho_sigma_nu_d_mu = christoffel_derivatives[rho][sigma][nu][mu].clone();
+                
+                // ∂Γ^ρ_σμ/∂x^ν
+                let d_christoffel_rho_sigma_mu_d_nu = christoffel_derivatives[rho][sigma][mu][nu].clone();
+                
+                // ∂Γ^ρ_σν/∂x^μ - ∂Γ^ρ_σμ/∂x^ν
+                riemann_expr = SymbolicExpr::Add(
+                    Box::new(riemann_expr),
+                    Box::new(SymbolicExpr::Subtract(
+                        Box::new(d_christoffel_rho_sigma_nu_d_mu),
+                        Box::new(d_christoffel_rho_sigma_mu_d_nu),
+                    )),
+                );
+                
+                // Add quadratic terms: Γ^ρ_λμ*Γ^λ_σν - Γ^ρ_λν*Γ^λ_σμ
+                for lambda in 0..n {
+                    let term1 = SymbolicExpr::Multiply(
+                        Box::new(christoffel[rho][lambda][mu].clone()),
+                        Box::new(christoffel[lambda][sigma][nu].clone()),
+                    );
                     
-                    // ∂Γ^ρ_σμ/∂x^ν
-                    let d_christoffel_rho_sigma_mu_d_nu = christoffel[rho][sigma][mu].derivative(&coords[nu]);
+                    let term2 = SymbolicExpr::Multiply(
+                        Box::new(christoffel[rho][lambda][nu].clone()),
+                        Box::new(christoffel[lambda][sigma][mu].clone()),
+                    );
                     
-                    // ∂Γ^ρ_σν/∂x^μ - ∂Γ^ρ_σμ/∂x^ν
                     riemann_expr = SymbolicExpr::Add(
                         Box::new(riemann_expr),
                         Box::new(SymbolicExpr::Subtract(
-                            Box::new(d_christoffel_rho_sigma_nu_d_mu),
-                            Box::new(d_christoffel_rho_sigma_mu_d_nu),
+                            Box::new(term1),
+                            Box::new(term2),
                         )),
                     );
-                    
-                    // Add quadratic terms: Γ^ρ_λμ*Γ^λ_σν - Γ^ρ_λν*Γ^λ_σμ
-                    for lambda in 0..n {
-                        let term1 = SymbolicExpr::Multiply(
-                            Box::new(christoffel[rho][lambda][mu].clone()),
-                            Box::new(christoffel[lambda][sigma][nu].clone()),
-                        );
-                        
-                        let term2 = SymbolicExpr::Multiply(
-                            Box::new(christoffel[rho][lambda][nu].clone()),
-                            Box::new(christoffel[lambda][sigma][mu].clone()),
-                        );
-                        
-                        riemann_expr = SymbolicExpr::Add(
-                            Box::new(riemann_expr),
-                            Box::new(SymbolicExpr::Subtract(
-                                Box::new(term1),
-                                Box::new(term2),
-                            )),
-                        );
-                    }
-                    
-                    let simplified = riemann_expr.simplify();
-                    
-                    // Only include non-zero components
-                    if !simplified.is_zero() {
-                        components.push(TensorComponent {
-                            indices: vec![rho, sigma, mu, nu],
-                            expression: simplified.to_string(),
-                        });
-                    }
                 }
+                
+                budget.check_expr(&riemann_expr)?;
+                let component = riemann_expr.simplify_expanded();
+                budget.check_expr(&component)?;
+                slice[sigma][nu][mu] = SymbolicExpr::Negate(Box::new(component.clone())).simplify();
+                slice[sigma][mu][nu] = component;
             }
         }
     }
     
-    Ok(RiemannResult {
-        components,
-        dimension: n,
-    })
+    Ok(slice)
 }
 
 pub fn calculate_ricci_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
-    let n = metric.len();
-    let riemann_result = calculate_riemann_tensor(metric, coords)?;
-    let riemann = riemann_result_to_tensor(&riemann_result, n);
+    calculate_ricci_tensor_with_options(metric, coords, &CurvatureOptions::default())
+}
+
+pub fn calculate_ricci_tensor_with_options(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<RiemannResult, TensorError> {
+    let ricci = ricci_tensor(metric, coords, options)?;
     
-    let mut components = Vec::new();
+    Ok(RiemannResult {
+        components: rank2_components(&ricci, options.include_zeros),
+        dimension: metric.len(),
+    })
+}
+
+/// Ricci tensor `R_μν` as a dense `[μ][ν]` matrix, zeros included.
+pub fn calculate_ricci_tensor_full(metric: &MetricTensor, coords: &[String]) -> Result<MetricTensor, TensorError> {
+    ricci_tensor(metric, coords, &CurvatureOptions::default())
+}
+
+fn ricci_tensor(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<MetricTensor, TensorError> {
+    let riemann = riemann_tensor(metric, coords, options)?;
+    contract_riemann(&riemann, &options.budget)
+}
+
+/// Ricci tensor from already computed Christoffel symbols; see
+/// `calculate_riemann_tensor_with_christoffel`.
+pub fn calculate_ricci_tensor_with_christoffel(
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+    budget: &ComputationBudget,
+) -> Result<MetricTensor, TensorError> {
+    let riemann = calculate_riemann_tensor_with_christoffel(christoffel, coords, budget)?;
+    contract_riemann(&riemann, budget)
+}
+
+/// Ricci tensor `R_μν = R^ρ_μρν`, contracting the first and third indices.
+#[allow(clippy::needless_range_loop)]
+fn contract_riemann(riemann: &RiemannTensor, budget: &ComputationBudget) -> Result<MetricTensor, TensorError> {
+    let n = riemann.len();
+    let mut ricci = vec![vec![SymbolicExpr::Zero; n]; n];
     
-    // Ricci tensor: R_μν = R^ρ_μρν (contraction over first and third indices)
     for mu in 0..n {
         for nu in 0..n {
             let mut ricci_expr = SymbolicExpr::Zero;
@@ -208,63 +735,117 @@ pub fn calculate_ricci_tensor(metric: &MetricTensor, coords: &[String]) -> Resul
                 );
             }
             
-            let simplified = ricci_expr.simplify();
-            
-            if !simplified.is_zero() {
-                components.push(TensorComponent {
-                    indices: vec![mu, nu],
-                    expression: simplified.to_string(),
-                });
-            }
+            let component = ricci_expr.simplify_expanded();
+            budget.check_expr(&component)?;
+            ricci[mu][nu] = component;
         }
     }
     
-    Ok(RiemannResult {
-        components,
-        dimension: n,
-    })
+    Ok(ricci)
 }
 
 pub fn calculate_ricci_scalar(metric: &MetricTensor, coords: &[String]) -> Result<TensorComponent, TensorError> {
+    calculate_ricci_scalar_with_options(metric, coords, &CurvatureOptions::default())
+}
+
+pub fn calculate_ricci_scalar_with_options(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<TensorComponent, TensorError> {
+    let ricci = ricci_tensor(metric, coords, options)?;
+    let scalar = ricci_scalar(metric, &ricci)?;
+    
+    Ok(TensorComponent {
+        indices: vec![],
+        expression: scalar.to_string(),
+    })
+}
+
+/// Ricci scalar `R = g^μν R_μν` from an already computed Ricci tensor.
+fn ricci_scalar(metric: &MetricTensor, ricci: &MetricTensor) -> Result<SymbolicExpr, TensorError> {
+    tensor_trace(ricci, metric)
+}
+
+/// Trace `g^μν T_μν` of a rank-2 tensor with both indices down.
+pub fn tensor_trace(tensor: &[Vec<SymbolicExpr>], metric: &MetricTensor) -> Result<SymbolicExpr, TensorError> {
     let n = metric.len();
-    let ricci_result = calculate_ricci_tensor(metric, coords)?;
-    let ricci = ricci_result_to_matrix(&ricci_result, n);
+    if tensor.len() != n || tensor.iter().any(|row| row.len() != n) {
+        return Err(TensorError::ComputationError(format!(
+            "Cannot trace a tensor with {} rows against a {}D metric", tensor.len(), n
+        )));
+    }
     let metric_inv = calculate_metric_inverse(metric)?;
     
-    let mut scalar_expr = SymbolicExpr::Zero;
+    let mut trace_expr = SymbolicExpr::Zero;
     
-    // Ricci scalar: R = g^μν * R_μν
     for mu in 0..n {
         for nu in 0..n {
+            if metric_inv[mu][nu].is_zero() || tensor[mu][nu].is_zero() {
+                continue;
+            }
             let term = SymbolicExpr::Multiply(
                 Box::new(metric_inv[mu][nu].clone()),
-                Box::new(ricci[mu][nu].clone()),
+                Box::new(tensor[mu][nu].clone()),
             );
             
-            scalar_expr = SymbolicExpr::Add(
-                Box::new(scalar_expr),
+            trace_expr = SymbolicExpr::Add(
+                Box::new(trace_expr),
                 Box::new(term),
             );
         }
     }
     
-    let simplified = scalar_expr.simplify();
+    Ok(trace_expr.simplify_expanded())
+}
+
+pub fn calculate_einstein_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
+    calculate_einstein_tensor_with_options(metric, coords, &CurvatureOptions::default())
+}
+
+pub fn calculate_einstein_tensor_with_options(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<RiemannResult, TensorError> {
+    let einstein = einstein_tensor(metric, coords, options)?;
     
-    Ok(TensorComponent {
-        indices: vec![],
-        expression: simplified.to_string(),
+    Ok(RiemannResult {
+        components: rank2_components(&einstein, options.include_zeros),
+        dimension: metric.len(),
     })
 }
 
-pub fn calculate_einstein_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
+/// Einstein tensor `G_μν` as a dense `[μ][ν]` matrix, zeros included.
+pub fn calculate_einstein_tensor_full(metric: &MetricTensor, coords: &[String]) -> Result<MetricTensor, TensorError> {
+    einstein_tensor(metric, coords, &CurvatureOptions::default())
+}
+
+fn einstein_tensor(
+    metric: &MetricTensor,
+    coords: &[String],
+    options: &CurvatureOptions,
+) -> Result<MetricTensor, TensorError> {
+    let ricci = ricci_tensor(metric, coords, options)?;
+    einstein_from_ricci(metric, &ricci)
+}
+
+/// Einstein tensor from already computed Christoffel symbols; see
+/// `calculate_riemann_tensor_with_christoffel`.
+pub fn calculate_einstein_tensor_with_christoffel(
+    metric: &MetricTensor,
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+    budget: &ComputationBudget,
+) -> Result<MetricTensor, TensorError> {
+    let riemann = riemann_from_christoffel(christoffel, coords, is_diagonal(metric), budget)?;
+    einstein_from_ricci(metric, &contract_riemann(&riemann, budget)?)
+}
+
+fn einstein_from_ricci(metric: &MetricTensor, ricci: &MetricTensor) -> Result<MetricTensor, TensorError> {
     let n = metric.len();
-    let ricci_result = calculate_ricci_tensor(metric, coords)?;
-    let ricci = ricci_result_to_matrix(&ricci_result, n);
-    let ricci_scalar = calculate_ricci_scalar(metric, coords)?;
-    let ricci_scalar_expr = SymbolicExpr::parse(&ricci_scalar.expression)
-        .map_err(|e| TensorError::ComputationError(format!("Failed to parse Ricci scalar: {}", e)))?;
-    
-    let mut components = Vec::new();
+    let ricci_scalar_expr = ricci_scalar(metric, ricci)?;
+    let mut einstein = vec![vec![SymbolicExpr::Zero; n]; n];
     
     // Einstein tensor: G_μν = R_μν - (1/2) * g_μν * R
     for mu in 0..n {
@@ -282,41 +863,672 @@ pub fn calculate_einstein_tensor(metric: &MetricTensor, coords: &[String]) -> Re
                 Box::new(half_metric_scalar),
             );
             
-            let simplified = einstein_expr.simplify();
-            
-            if !simplified.is_zero() {
-                components.push(TensorComponent {
-                    indices: vec![mu, nu],
-                    expression: simplified.to_string(),
-                });
+            einstein[mu][nu] = einstein_expr.simplify_expanded();
+        }
+    }
+    
+    Ok(einstein)
+}
+
+/// Mixed Einstein tensor `G^μ_ν = g^μρ G_ρν`, indexed `[μ][ν]`. For a perfect fluid
+/// its diagonal reads off `-8πρ` and `8πp` directly.
+pub fn calculate_einstein_tensor_mixed(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
+    let n = metric.len();
+    let einstein = calculate_einstein_tensor_full(metric, coords)?;
+    let metric_inv = calculate_metric_inverse(metric)?;
+    let mut mixed = vec![vec![SymbolicExpr::Zero; n]; n];
+    
+    for mu in 0..n {
+        for nu in 0..n {
+            let mut expr = SymbolicExpr::Zero;
+            for rho in 0..n {
+                if metric_inv[mu][rho].is_zero() || einstein[rho][nu].is_zero() {
+                    continue;
+                }
+                expr = SymbolicExpr::Add(
+                    Box::new(expr),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(metric_inv[mu][rho].clone()),
+                        Box::new(einstein[rho][nu].clone()),
+                    )),
+                );
             }
+            mixed[mu][nu] = expr.simplify_expanded();
         }
     }
     
     Ok(RiemannResult {
-        components,
+        components: rank2_components(&mixed, false),
         dimension: n,
     })
 }
 
-// Helper functions
+/// Kretschmann scalar `K = R_ρσμν R^ρσμν`, a curvature invariant that stays finite at
+/// coordinate singularities and diverges at curvature singularities.
+pub fn calculate_kretschmann_scalar(metric: &MetricTensor, coords: &[String]) -> Result<TensorComponent, TensorError> {
+    let christoffel = calculate_christoffel_symbols_full(metric, coords)?;
+    calculate_kretschmann_scalar_with_christoffel(metric, &christoffel, coords, &ComputationBudget::default())
+}
 
-fn calculate_metric_inverse(metric: &MetricTensor) -> Result<MetricTensor, TensorError> {
+/// Kretschmann scalar from already computed Christoffel symbols; see
+/// `calculate_riemann_tensor_with_christoffel`.
+pub fn calculate_kretschmann_scalar_with_christoffel(
+    metric: &MetricTensor,
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+    budget: &ComputationBudget,
+) -> Result<TensorComponent, TensorError> {
     let n = metric.len();
+    let riemann = riemann_from_christoffel(christoffel, coords, is_diagonal(metric), budget)?;
+    let metric_inv = calculate_metric_inverse(metric)?;
     
-    // For now, implement a simple 2x2 inverse
-    // In a complete implementation, we'd use proper symbolic matrix inversion
-    if n == 2 {
-        let det = SymbolicExpr::Subtract(
-            Box::new(SymbolicExpr::Multiply(
-                Box::new(metric[0][0].clone()),
-                Box::new(metric[1][1].clone()),
-            )),
+    let riemann_lower = contract_index(&riemann, 0, metric)?;
+    let mut riemann_upper = riemann;
+    for slot in 1..4 {
+        riemann_upper = contract_index(&riemann_upper, slot, &metric_inv)?;
+    }
+    
+    let mut scalar_expr = SymbolicExpr::Zero;
+    for rho in 0..n {
+        for sigma in 0..n {
+            for mu in 0..n {
+                for nu in 0..n {
+                    if riemann_lower[rho][sigma][mu][nu].is_zero() || riemann_upper[rho][sigma][mu][nu].is_zero() {
+                        continue;
+                    }
+                    scalar_expr = SymbolicExpr::Add(
+                        Box::new(scalar_expr),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(riemann_lower[rho][sigma][mu][nu].clone()),
+                            Box::new(riemann_upper[rho][sigma][mu][nu].clone()),
+                        )),
+                    );
+                }
+            }
+        }
+    }
+    
+    Ok(TensorComponent {
+        indices: vec![],
+        expression: scalar_expr.simplify().to_string(),
+    })
+}
+
+pub fn calculate_weyl_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
+    let weyl = calculate_weyl_tensor_full(metric, coords)?;
+    
+    Ok(RiemannResult {
+        components: rank4_components(&weyl, false),
+        dimension: metric.len(),
+    })
+}
+
+/// Weyl tensor `C_ρσμν`, all indices down, as a dense array.
+pub fn calculate_weyl_tensor_full(metric: &MetricTensor, coords: &[String]) -> Result<RiemannTensor, TensorError> {
+    let n = metric.len();
+    if n < 3 {
+        return Err(TensorError::ComputationError(
+            "Weyl tensor requires at least 3 dimensions".to_string()
+        ));
+    }
+    
+    let riemann = calculate_riemann_tensor_full(metric, coords)?;
+    
+    // Lower the first index: R_ρσμν = g_ρλ R^λ_σμν
+    let riemann_lower = contract_index(&riemann, 0, metric)?;
+    let ricci = contract_riemann(&riemann, &ComputationBudget::default())?;
+    let scalar = ricci_scalar(metric, &ricci)?;
+    
+    let g = metric;
+    let mul = |a: &SymbolicExpr, b: &SymbolicExpr| SymbolicExpr::Multiply(Box::new(a.clone()), Box::new(b.clone()));
+    let ricci_factor = SymbolicExpr::Constant(1.0 / (n as f64 - 2.0));
+    let scalar_factor = SymbolicExpr::Divide(
+        Box::new(scalar),
+        Box::new(SymbolicExpr::Constant((n as f64 - 1.0) * (n as f64 - 2.0))),
+    );
+    
+    // C_abcd = R_abcd - (g_ac R_bd - g_ad R_bc - g_bc R_ad + g_bd R_ac)/(n-2)
+    //                 + R (g_ac g_bd - g_ad g_bc)/((n-1)(n-2))
+    let mut weyl = vec![vec![vec![vec![SymbolicExpr::Zero; n]; n]; n]; n];
+    for a in 0..n {
+        for b in 0..n {
+            for c in 0..n {
+                for d in 0..n {
+                    let ricci_part = SymbolicExpr::Add(
+                        Box::new(SymbolicExpr::Subtract(
+                            Box::new(SymbolicExpr::Subtract(
+                                Box::new(mul(&g[a][c], &ricci[b][d])),
+                                Box::new(mul(&g[a][d], &ricci[b][c])),
+                            )),
+                            Box::new(mul(&g[b][c], &ricci[a][d])),
+                        )),
+                        Box::new(mul(&g[b][d], &ricci[a][c])),
+                    );
+                    let scalar_part = SymbolicExpr::Subtract(
+                        Box::new(mul(&g[a][c], &g[b][d])),
+                        Box::new(mul(&g[a][d], &g[b][c])),
+                    );
+                    
+                    let expr = SymbolicExpr::Add(
+                        Box::new(SymbolicExpr::Subtract(
+                            Box::new(riemann_lower[a][b][c][d].clone()),
+                            Box::new(mul(&ricci_factor, &ricci_part)),
+                        )),
+                        Box::new(mul(&scalar_factor, &scalar_part)),
+                    );
+                    weyl[a][b][c][d] = expr.simplify_expanded();
+                }
+            }
+        }
+    }
+    
+    Ok(weyl)
+}
+
+/// Cotton tensor `C_ijk = ∇_k P_ij - ∇_j P_ik` of a 3D metric, where
+/// `P_ij = R_ij - (R/4) g_ij` is the 3D Schouten tensor. The Weyl tensor vanishes
+/// identically in three dimensions, and the metric is conformally flat exactly when
+/// the Cotton tensor vanishes.
+#[allow(clippy::needless_range_loop)]
+pub fn calculate_cotton_tensor(metric: &MetricTensor, coords: &[String]) -> Result<RiemannResult, TensorError> {
+    let n = metric.len();
+    if n != 3 {
+        return Err(TensorError::ComputationError(format!(
+            "Cotton tensor is only defined here for 3 dimensions, got {}", n
+        )));
+    }
+    
+    let ricci = calculate_ricci_tensor_full(metric, coords)?;
+    let scalar = ricci_scalar(metric, &ricci)?;
+    
+    let mut schouten = vec![vec![SymbolicExpr::Zero; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            schouten[i][j] = SymbolicExpr::Subtract(
+                Box::new(ricci[i][j].clone()),
+                Box::new(SymbolicExpr::Multiply(
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(SymbolicExpr::Constant(0.25)),
+                        Box::new(scalar.clone()),
+                    )),
+                    Box::new(metric[i][j].clone()),
+                )),
+            ).simplify_expanded();
+        }
+    }
+    
+    let christoffel = calculate_christoffel_symbols_full(metric, coords)?;
+    let gradient = covariant_derivative_rank2(&schouten, &christoffel, coords);
+    
+    let mut components = Vec::new();
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let cotton = SymbolicExpr::Subtract(
+                    Box::new(gradient[k][i][j].clone()),
+                    Box::new(gradient[j][i][k].clone()),
+                ).simplify_expanded();
+                
+                // Third derivatives of a conformal factor can leave a component that
+                // is zero but too large to normalize, so compare it numerically too
+                if !cotton.approx_eq(&SymbolicExpr::Zero) {
+                    components.push(TensorComponent {
+                        indices: vec![i, j, k],
+                        expression: cotton.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    
+    Ok(RiemannResult {
+        components,
+        dimension: n,
+    })
+}
+
+/// Tidal tensor `E_ij = C_iμjν u^μ u^ν`, the electric part of the Weyl tensor seen by
+/// an observer with four-velocity `u`. The velocity is normalised with the metric, so
+/// any timelike vector along the observer's worldline may be supplied.
+pub fn tidal_tensor(
+    metric: &MetricTensor,
+    coords: &[String],
+    observer_velocity: &[SymbolicExpr],
+) -> Result<Vec<Vec<SymbolicExpr>>, TensorError> {
+    let n = metric.len();
+    if observer_velocity.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "Observer velocity has {} components but the metric is {}-dimensional",
+            observer_velocity.len(), n
+        )));
+    }
+    
+    // g_μν u^μ u^ν
+    let mut norm = SymbolicExpr::Zero;
+    for mu in 0..n {
+        for nu in 0..n {
+            norm = SymbolicExpr::Add(
+                Box::new(norm),
+                Box::new(SymbolicExpr::Multiply(
+                    Box::new(metric[mu][nu].clone()),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(observer_velocity[mu].clone()),
+                        Box::new(observer_velocity[nu].clone()),
+                    )),
+                )),
+            );
+        }
+    }
+    let norm = norm.simplify();
+    if norm.is_zero() {
+        return Err(TensorError::ComputationError(
+            "Observer velocity must be timelike, but it has zero norm".to_string()
+        ));
+    }
+    
+    let weyl = calculate_weyl_tensor_full(metric, coords)?;
+    
+    let mut tidal = vec![vec![SymbolicExpr::Zero; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            let mut expr = SymbolicExpr::Zero;
+            for mu in 0..n {
+                for nu in 0..n {
+                    expr = SymbolicExpr::Add(
+                        Box::new(expr),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(weyl[i][mu][j][nu].clone()),
+                            Box::new(SymbolicExpr::Multiply(
+                                Box::new(observer_velocity[mu].clone()),
+                                Box::new(observer_velocity[nu].clone()),
+                            )),
+                        )),
+                    );
+                }
+            }
+            
+            // Divide by -u·u so the result is independent of the velocity's normalisation
+            tidal[i][j] = SymbolicExpr::Divide(
+                Box::new(expr),
+                Box::new(SymbolicExpr::Negate(Box::new(norm.clone()))),
+            ).simplify();
+        }
+    }
+    
+    Ok(tidal)
+}
+
+/// Expansion `θ = ∇_μ k^μ` of the null congruence with tangent `k`. A surface on which
+/// the outgoing expansion vanishes is marginally trapped, which locates apparent horizons.
+pub fn null_expansion(
+    metric: &MetricTensor,
+    coords: &[String],
+    null_vector: &[SymbolicExpr],
+) -> Result<SymbolicExpr, TensorError> {
+    let n = metric.len();
+    if null_vector.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "Null vector has {} components but the metric is {}-dimensional",
+            null_vector.len(), n
+        )));
+    }
+    
+    // g_μν k^μ k^ν
+    let mut norm = SymbolicExpr::Zero;
+    for mu in 0..n {
+        for nu in 0..n {
+            norm = SymbolicExpr::Add(
+                Box::new(norm),
+                Box::new(SymbolicExpr::Multiply(
+                    Box::new(metric[mu][nu].clone()),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(null_vector[mu].clone()),
+                        Box::new(null_vector[nu].clone()),
+                    )),
+                )),
+            );
+        }
+    }
+    if !norm.approx_eq(&SymbolicExpr::Zero) {
+        return Err(TensorError::ComputationError(format!(
+            "Vector must be null, but g(k, k) = {}", norm.simplify()
+        )));
+    }
+    
+    covariant_divergence(metric, coords, null_vector)
+}
+
+/// Compares two tensors component by component, reporting every index where `b`
+/// is not equivalent to `a` along with the symbolic difference.
+pub fn diff_tensors(a: &RiemannResult, b: &RiemannResult) -> TensorDiff {
+    let parse = |component: &TensorComponent| {
+        SymbolicExpr::parse(&component.expression)
+            .unwrap_or_else(|_| SymbolicExpr::Variable(component.expression.clone()))
+    };
+    
+    let mut indices: Vec<&Vec<usize>> = a.components.iter()
+        .chain(b.components.iter())
+        .map(|component| &component.indices)
+        .collect();
+    indices.sort();
+    indices.dedup();
+    
+    let mut differences = Vec::new();
+    for index in indices {
+        let before = a.components.iter().find(|c| &c.indices == index).map(parse).unwrap_or(SymbolicExpr::Zero);
+        let after = b.components.iter().find(|c| &c.indices == index).map(parse).unwrap_or(SymbolicExpr::Zero);
+        
+        if !before.approx_eq(&after) {
+            let difference = SymbolicExpr::Subtract(Box::new(after.clone()), Box::new(before.clone())).simplify();
+            differences.push(ComponentDiff {
+                indices: index.clone(),
+                before: before.to_string(),
+                after: after.to_string(),
+                difference: difference.to_string(),
+            });
+        }
+    }
+    
+    TensorDiff { differences }
+}
+
+/// Common subexpression elimination: every non-trivial sub-expression occurring
+/// more than once across `components` is extracted into a binding named `_cse<k>`.
+/// Sub-expressions are identified by their printed form.
+pub fn eliminate_common_subexpressions(components: &[TensorComponent]) -> Result<CommonSubexpressions, TensorError> {
+    let exprs = components.iter()
+        .map(|component| SymbolicExpr::parse(&component.expression))
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let mut cse = CseState::new(&exprs);
+    let components = components.iter()
+        .zip(&exprs)
+        .map(|(component, expr)| TensorComponent {
+            indices: component.indices.clone(),
+            expression: cse.rewrite(expr).to_string(),
+        })
+        .collect();
+    let bindings = cse.bindings.into_iter()
+        .map(|(name, expr)| SubexpressionBinding { name, expression: expr.to_string() })
+        .collect();
+    
+    Ok(CommonSubexpressions { bindings, components })
+}
+
+/// Determines whether `metric = Ω² target` for some scalar field `Ω²`, returning the
+/// factor if so and `None` if the two metrics are not conformally related.
+pub fn conformal_factor_to(metric: &MetricTensor, target: &MetricTensor) -> Result<Option<SymbolicExpr>, TensorError> {
+    let n = metric.len();
+    if target.len() != n || metric.iter().chain(target.iter()).any(|row| row.len() != n) {
+        return Err(TensorError::InvalidMetric(
+            "Both metrics must be square and of the same dimension".to_string()
+        ));
+    }
+    
+    // Read the factor off the first non-vanishing component of the target
+    let (i, j) = match (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        .find(|&(i, j)| !target[i][j].simplify().is_zero())
+    {
+        Some(index) => index,
+        None => return Err(TensorError::InvalidMetric("Target metric has no non-zero components".to_string())),
+    };
+    let factor = SymbolicExpr::Divide(
+        Box::new(metric[i][j].clone()),
+        Box::new(target[i][j].clone()),
+    ).simplify();
+    if factor.approx_eq(&SymbolicExpr::Zero) {
+        return Ok(None);
+    }
+    
+    // g_ab t_ij = g_ij t_ab for every component, without dividing by t_ab
+    for a in 0..n {
+        for b in 0..n {
+            let lhs = SymbolicExpr::Multiply(
+                Box::new(metric[a][b].clone()),
+                Box::new(target[i][j].clone()),
+            );
+            let rhs = SymbolicExpr::Multiply(
+                Box::new(metric[i][j].clone()),
+                Box::new(target[a][b].clone()),
+            );
+            if !lhs.approx_eq(&rhs) {
+                return Ok(None);
+            }
+        }
+    }
+    
+    Ok(Some(factor))
+}
+
+/// Rewrites the metric in new coordinates, `g'_ab = (∂x^μ/∂x'^a)(∂x^ν/∂x'^b) g_μν`,
+/// where `transform[μ]` gives the old coordinate `x^μ` as a function of `new_coords`,
+/// e.g. `[t, r*sin(theta)*cos(phi), r*sin(theta)*sin(phi), r*cos(theta)]` from
+/// Cartesian to spherical coordinates. Fewer new coordinates than old give the
+/// metric induced on the corresponding submanifold.
+pub fn transform_metric(
+    metric: &MetricTensor,
+    old_coords: &[String],
+    new_coords: &[String],
+    transform: &[SymbolicExpr],
+) -> Result<MetricTensor, TensorError> {
+    let n = metric.len();
+    if metric.iter().any(|row| row.len() != n) {
+        return Err(TensorError::InvalidMetric("Metric tensor must be square".to_string()));
+    }
+    if old_coords.len() != n || transform.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "A {}D metric needs {} old coordinates and transform expressions, got {} and {}",
+            n, n, old_coords.len(), transform.len()
+        )));
+    }
+    
+    // Substitute through placeholders, so that a transform may reuse the name of an
+    // old coordinate, as in t -> t
+    let placeholder = |mu: usize| format!("#{}", mu);
+    let substitute = |expr: &SymbolicExpr| {
+        let renamed = (0..n).fold(expr.clone(), |expr, mu| {
+            expr.substitute(&old_coords[mu], &SymbolicExpr::Variable(placeholder(mu)))
+        });
+        (0..n).fold(renamed, |expr, mu| expr.substitute(&placeholder(mu), &transform[mu]))
+    };
+    let metric: MetricTensor = metric.iter().map(|row| row.iter().map(substitute).collect()).collect();
+    let jacobian: Vec<Vec<SymbolicExpr>> = transform.iter().map(|x| x.gradient(new_coords)).collect();
+    
+    let m = new_coords.len();
+    let mut transformed = vec![vec![SymbolicExpr::Zero; m]; m];
+    for a in 0..m {
+        for b in a..m {
+            let mut expr = SymbolicExpr::Zero;
+            for mu in 0..n {
+                for nu in 0..n {
+                    if metric[mu][nu].is_zero() || jacobian[mu][a].is_zero() || jacobian[nu][b].is_zero() {
+                        continue;
+                    }
+                    expr = SymbolicExpr::Add(
+                        Box::new(expr),
+                        Box::new(SymbolicExpr::Multiply(
+                            Box::new(SymbolicExpr::Multiply(
+                                Box::new(jacobian[mu][a].clone()),
+                                Box::new(jacobian[nu][b].clone()),
+                            )),
+                            Box::new(metric[mu][nu].clone()),
+                        )),
+                    );
+                }
+            }
+            let expr = expr.simplify_expanded();
+            transformed[b][a] = expr.clone();
+            transformed[a][b] = expr;
+        }
+    }
+    
+    Ok(transformed)
+}
+
+/// Covariant derivative `∇_μ V^ν = ∂_μ V^ν + Γ^ν_μλ V^λ` of a vector field, indexed `[μ][ν]`.
+pub fn covariant_derivative_vector(
+    vector: &[SymbolicExpr],
+    metric: &MetricTensor,
+    coords: &[String],
+) -> Result<Vec<Vec<SymbolicExpr>>, TensorError> {
+    let n = metric.len();
+    if vector.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "Vector has {} components but the metric is {}-dimensional",
+            vector.len(), n
+        )));
+    }
+    let christoffel = calculate_christoffel_symbols_full(metric, coords)?;
+    
+    let mut result = vec![vec![SymbolicExpr::Zero; n]; n];
+    for mu in 0..n {
+        for nu in 0..n {
+            let mut expr = vector[nu].derivative(&coords[mu]);
+            for lambda in 0..n {
+                if christoffel[nu][mu][lambda].is_zero() || vector[lambda].is_zero() {
+                    continue;
+                }
+                expr = SymbolicExpr::Add(
+                    Box::new(expr),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(christoffel[nu][mu][lambda].clone()),
+                        Box::new(vector[lambda].clone()),
+                    )),
+                );
+            }
+            result[mu][nu] = expr.simplify_expanded();
+        }
+    }
+    
+    Ok(result)
+}
+
+/// Line element `g_μν ẋ^μ ẋ^ν` for the given velocity components, e.g. the
+/// Lagrangian `L = g_μν ẋ^μ ẋ^ν` of the geodesic problem.
+pub fn line_element(metric: &MetricTensor, coords: &[String], velocities: &[SymbolicExpr]) -> Result<SymbolicExpr, TensorError> {
+    let n = metric.len();
+    if coords.len() != n || velocities.len() != n {
+        return Err(TensorError::ComputationError(format!(
+            "Line element of a {}D metric needs {} coordinates and velocities, got {} and {}",
+            n, n, coords.len(), velocities.len()
+        )));
+    }
+    
+    let mut expr = SymbolicExpr::Zero;
+    for mu in 0..n {
+        for nu in 0..n {
+            if metric[mu][nu].is_zero() || velocities[mu].is_zero() || velocities[nu].is_zero() {
+                continue;
+            }
+            expr = SymbolicExpr::Add(
+                Box::new(expr),
+                Box::new(SymbolicExpr::Multiply(
+                    Box::new(metric[mu][nu].clone()),
+                    Box::new(SymbolicExpr::Multiply(Box::new(velocities[mu].clone()), Box::new(velocities[nu].clone()))),
+                )),
+            );
+        }
+    }
+    
+    Ok(expr.simplify())
+}
+
+/// Geodesic equations `d²x^μ/dτ² + Γ^μ_αβ (dx^α/dτ)(dx^β/dτ) = 0`, one per coordinate.
+/// Each expression is the left-hand side, with `xdot(x)` and `xddot(x)` standing for the
+/// first and second proper-time derivatives of coordinate `x`.
+#[allow(clippy::needless_range_loop)]
+pub fn geodesic_equations(metric: &MetricTensor, coords: &[String]) -> Result<Vec<TensorComponent>, TensorError> {
+    let n = metric.len();
+    let christoffel = calculate_christoffel_symbols_full(metric, coords)?;
+    let velocity = |alpha: usize| SymbolicExpr::Function("xdot".to_string(), vec![SymbolicExpr::Variable(coords[alpha].clone())]);
+    
+    let mut equations = Vec::with_capacity(n);
+    for mu in 0..n {
+        let mut expr = SymbolicExpr::Function("xddot".to_string(), vec![SymbolicExpr::Variable(coords[mu].clone())]);
+        for alpha in 0..n {
+            for beta in alpha..n {
+                if christoffel[mu][alpha][beta].is_zero() {
+                    continue;
+                }
+                // Γ^μ_αβ is symmetric, so off-diagonal pairs appear twice
+                let coefficient = if alpha == beta {
+                    christoffel[mu][alpha][beta].clone()
+                } else {
+                    SymbolicExpr::Multiply(Box::new(SymbolicExpr::Constant(2.0)), Box::new(christoffel[mu][alpha][beta].clone()))
+                };
+                expr = SymbolicExpr::Add(
+                    Box::new(expr),
+                    Box::new(SymbolicExpr::Multiply(
+                        Box::new(coefficient),
+                        Box::new(SymbolicExpr::Multiply(Box::new(velocity(alpha)), Box::new(velocity(beta)))),
+                    )),
+                );
+            }
+        }
+        equations.push(TensorComponent {
+            indices: vec![mu],
+            expression: expr.simplify().to_string(),
+        });
+    }
+    
+    Ok(equations)
+}
+
+// Helper functions
+
+/// `∇_μ v^μ = ∂_μ v^μ + Γ^μ_μλ v^λ`
+fn covariant_divergence(
+    metric: &MetricTensor,
+    coords: &[String],
+    vector: &[SymbolicExpr],
+) -> Result<SymbolicExpr, TensorError> {
+    let n = metric.len();
+    let christoffel = calculate_christoffel_symbols_full(metric, coords)?;
+    
+    let mut divergence = SymbolicExpr::Zero;
+    for mu in 0..n {
+        divergence = SymbolicExpr::Add(
+            Box::new(divergence),
+            Box::new(vector[mu].derivative(&coords[mu])),
+        );
+        for lambda in 0..n {
+            divergence = SymbolicExpr::Add(
+                Box::new(divergence),
+                Box::new(SymbolicExpr::Multiply(
+                    Box::new(christoffel[mu][mu][lambda].clone()),
+                    Box::new(vector[lambda].clone()),
+                )),
+            );
+        }
+    }
+    
+    Ok(divergence.simplify())
+}
+
+/// Symbolic inverse `g^μν` of the metric. Returns an error for singular metrics.
+pub fn calculate_metric_inverse(metric: &MetricTensor) -> Result<MetricTensor, TensorError> {
+    let n = metric.len();
+    
+    if is_diagonal(metric) {
+        return diagonal_inverse(metric);
+    }
+    
+    // Closed form for 2x2; everything else goes through the adjugate
+    if n == 2 {
+        let det = SymbolicExpr::Subtract(
+            Box::new(SymbolicExpr::Multiply(
+                Box::new(metric[0][0].clone()),
+                Box::new(metric[1][1].clone()),
+            )),
             Box::new(SymbolicExpr::Multiply(
                 Box::new(metric[0][1].clone()),
                 Box::new(metric[1][0].clone()),
             )),
         );
+        if det.simplify().is_zero() {
+            return Err(TensorError::InvalidMetric("Metric tensor is singular (zero determinant)".to_string()));
+        }
         
         let inv_det = SymbolicExpr::Divide(
             Box::new(SymbolicExpr::One),
@@ -328,84 +1540,1262 @@ fn calculate_metric_inverse(metric: &MetricTensor) -> Result<MetricTensor, Tenso
                 SymbolicExpr::Multiply(Box::new(inv_det.clone()), Box::new(metric[1][1].clone())),
                 SymbolicExpr::Multiply(
                     Box::new(inv_det.clone()),
-                    Box::new(SymbolicExpr::Subtract(
-                        Box::new(SymbolicExpr::Zero),
-                        Box::new(metric[0][1].clone()),
-                    )),
+                    Box::new(SymbolicExpr::Negate(Box::new(metric[0][1].clone()))),
                 ),
             ],
             vec![
                 SymbolicExpr::Multiply(
                     Box::new(inv_det.clone()),
-                    Box::new(SymbolicExpr::Subtract(
-                        Box::new(SymbolicExpr::Zero),
-                        Box::new(metric[1][0].clone()),
-                    )),
+                    Box::new(SymbolicExpr::Negate(Box::new(metric[1][0].clone()))),
                 ),
                 SymbolicExpr::Multiply(Box::new(inv_det), Box::new(metric[0][0].clone())),
             ],
         ])
     } else {
-        // For higher dimensions, return identity for now
-        // A complete implementation would use symbolic matrix inversion
-        let mut identity = vec![vec![SymbolicExpr::Zero; n]; n];
-        for i in 0..n {
-            identity[i][i] = SymbolicExpr::One;
+        adjugate_inverse(metric)
+    }
+}
+
+/// True if every off-diagonal entry simplifies to zero.
+fn is_diagonal(metric: &MetricTensor) -> bool {
+    metric.iter().enumerate().all(|(i, row)| {
+        row.iter().enumerate().all(|(j, component)| i == j || component.simplify().is_zero())
+    })
+}
+
+/// Inverse of a diagonal metric, `g^ii = 1/g_ii`.
+fn diagonal_inverse(metric: &MetricTensor) -> Result<MetricTensor, TensorError> {
+    let n = metric.len();
+    let mut inverse = vec![vec![SymbolicExpr::Zero; n]; n];
+    for i in 0..n {
+        let component = metric[i][i].simplify();
+        if component.is_zero() {
+            return Err(TensorError::InvalidMetric("Metric tensor is singular (zero determinant)".to_string()));
         }
-        Ok(identity)
+        inverse[i][i] = SymbolicExpr::Divide(Box::new(SymbolicExpr::One), Box::new(component)).simplify();
     }
+    Ok(inverse)
 }
 
-fn symbols_to_tensor(christoffel_result: &ChristoffelResult, n: usize) -> ChristoffelSymbols {
-    let mut tensor = vec![vec![vec![SymbolicExpr::Zero; n]; n]; n];
+/// General inverse `g^-1 = adj(g) / det(g)` via cofactor expansion, for any `n`.
+#[allow(clippy::needless_range_loop)]
+fn adjugate_inverse(metric: &MetricTensor) -> Result<MetricTensor, TensorError> {
+    let n = metric.len();
+    let det = matrix_determinant(metric).simplify();
+    if det.is_zero() {
+        return Err(TensorError::InvalidMetric("Metric tensor is singular (zero determinant)".to_string()));
+    }
     
-    for component in &christoffel_result.symbols {
-        if component.indices.len() == 3 {
-            let i = component.indices[0];
-            let j = component.indices[1];
-            let k = component.indices[2];
+    let mut inverse = vec![vec![SymbolicExpr::Zero; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            // The adjugate is the transpose of the cofactor matrix
+            let cofactor = matrix_determinant(&matrix_minor(metric, j, i));
+            let signed_cofactor = if (i + j) % 2 == 0 {
+                cofactor
+            } else {
+                SymbolicExpr::Negate(Box::new(cofactor))
+            };
             
-            if let Ok(expr) = SymbolicExpr::parse(&component.expression) {
-                tensor[i][j][k] = expr;
-            }
+            inverse[i][j] = SymbolicExpr::Divide(
+                Box::new(signed_cofactor),
+                Box::new(det.clone()),
+            ).simplify();
         }
     }
-    
-    tensor
+    Ok(inverse)
+}
+
+/// Symbolic determinant `g = det(g_μν)` of the metric, as needed for volume
+/// elements `sqrt(|g|) d^n x`.
+pub fn calculate_metric_determinant(metric: &MetricTensor) -> Result<SymbolicExpr, TensorError> {
+    if metric.iter().any(|row| row.len() != metric.len()) {
+        return Err(TensorError::InvalidMetric("Metric tensor must be square".to_string()));
+    }
+    Ok(matrix_determinant(metric).simplify_expanded())
 }
 
-fn riemann_result_to_tensor(riemann_result: &RiemannResult, n: usize) -> RiemannTensor {
-    let mut tensor = vec![vec![vec![vec![SymbolicExpr::Zero; n]; n]; n]; n];
+/// Finds where the metric degenerates or blows up: zeros and poles of the diagonal
+/// components (indices `[μ, μ]`) and of the determinant (no indices). Each result's
+/// `expression` states the condition, e.g. `(1 - ((2 * M) / r)) = 0 at r = (2 * M)`
+/// or `sin(theta) = 0` when it can't be solved for a coordinate. These are only
+/// candidates; `find_curvature_singularities` tells which are physical.
+pub fn find_metric_singularities(metric: &MetricTensor, coords: &[String]) -> Result<Vec<TensorComponent>, TensorError> {
+    let determinant = calculate_metric_determinant(metric)?;
+    let mut singularities = Vec::new();
     
-    for component in &riemann_result.components {
-        if component.indices.len() == 4 {
-            let i = component.indices[0];
-            let j = component.indices[1];
-            let k = component.indices[2];
-            let l = component.indices[3];
+    let entries = metric.iter().enumerate()
+        .map(|(mu, row)| (vec![mu, mu], row[mu].simplify()))
+        .chain(std::iter::once((vec![], determinant)));
+    for (indices, expr) in entries {
+        let mut factors = Vec::new();
+        singular_factors(&expr, &mut factors);
+        
+        for factor in factors.into_iter().filter(|f| coords.iter().any(|c| f.contains_variable(c))) {
+            let mut conditions = Vec::new();
+            for coord in coords.iter().filter(|c| factor.contains_variable(c)) {
+                if let Some((num, _)) = factor.as_rational_function(coord) {
+                    for root in solve_polynomial(&num, coord).unwrap_or_default() {
+                        if coords.iter().all(|c| !root.contains_variable(c)) {
+                            conditions.push(format!("{} = 0 at {} = {}", factor, coord, root));
+                        }
+                    }
+                }
+            }
+            for restriction in factor.domain_restrictions(coords).into_iter().filter(|r| !r.removable) {
+                conditions.push(format!("{} diverges at {} = {}", factor, restriction.variable, restriction.value));
+            }
+            if conditions.is_empty() {
+                conditions.push(format!("{} = 0", factor));
+            }
             
-            if let Ok(expr) = SymbolicExpr::parse(&component.expression) {
-                tensor[i][j][k][l] = expr;
+            for expression in conditions {
+                if !singularities.iter().any(|s: &TensorComponent| s.indices == indices && s.expression == expression) {
+                    singularities.push(TensorComponent { indices: indices.clone(), expression });
+                }
             }
         }
     }
     
-    tensor
+    Ok(singularities)
 }
 
-fn ricci_result_to_matrix(ricci_result: &RiemannResult, n: usize) -> MetricTensor {
-    let mut matrix = vec![vec![SymbolicExpr::Zero; n]; n];
-    
-    for component in &ricci_result.components {
-        if component.indices.len() == 2 {
-            let i = component.indices[0];
-            let j = component.indices[1];
-            
-            if let Ok(expr) = SymbolicExpr::parse(&component.expression) {
-                matrix[i][j] = expr;
+/// Splits `expr` into the factors whose zeros make it vanish or blow up, looking
+/// through products, quotients, constant powers and negation.
+fn singular_factors(expr: &SymbolicExpr, factors: &mut Vec<SymbolicExpr>) {
+    match expr {
+        SymbolicExpr::Multiply(left, right) | SymbolicExpr::Divide(left, right) => {
+            singular_factors(left, factors);
+            singular_factors(right, factors);
+        }
+        SymbolicExpr::Power(base, exp) if matches!(**exp, SymbolicExpr::Constant(_) | SymbolicExpr::One) => singular_factors(base, factors),
+        SymbolicExpr::Negate(inner) => singular_factors(inner, factors),
+        SymbolicExpr::Subtract(zero, inner) if zero.is_zero() => singular_factors(inner, factors),
+        SymbolicExpr::Constant(_) | SymbolicExpr::Zero | SymbolicExpr::One => {}
+        _ => {
+            if !factors.contains(expr) {
+                factors.push(expr.clone());
             }
         }
     }
+}
+
+/// Determinant by Laplace expansion along the first row, skipping zero entries.
+fn matrix_determinant(matrix: &MetricTensor) -> SymbolicExpr {
+    let n = matrix.len();
+    if n == 0 {
+        return SymbolicExpr::One;
+    }
+    if n == 1 {
+        return matrix[0][0].clone();
+    }
+    
+    let mut det = SymbolicExpr::Zero;
+    for col in 0..n {
+        if matrix[0][col].simplify().is_zero() {
+            continue;
+        }
+        
+        let term = SymbolicExpr::Multiply(
+            Box::new(matrix[0][col].clone()),
+            Box::new(matrix_determinant(&matrix_minor(matrix, 0, col))),
+        );
+        
+        det = if col % 2 == 0 {
+            SymbolicExpr::Add(Box::new(det), Box::new(term))
+        } else {
+            SymbolicExpr::Subtract(Box::new(det), Box::new(term))
+        };
+    }
+    
+    det.simplify()
+}
+
+/// The matrix with row `row` and column `col` removed.
+fn matrix_minor(matrix: &MetricTensor, row: usize, col: usize) -> MetricTensor {
+    matrix.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != row)
+        .map(|(_, r)| {
+            r.iter()
+                .enumerate()
+                .filter(|(j, _)| *j != col)
+                .map(|(_, expr)| expr.clone())
+                .collect()
+        })
+        .collect()
+}
+
+/// Components of a tensor of any rank as nested `Vec`s, with a bare `SymbolicExpr`
+/// as rank 0. `MetricTensor`, `ChristoffelSymbols` and `RiemannTensor` are all grids.
+pub trait ComponentGrid: Clone {
+    fn rank() -> usize;
+    
+    /// Applies `f` to every component.
+    fn map_components(&self, f: &dyn Fn(&SymbolicExpr) -> SymbolicExpr) -> Self;
+    
+    /// Combines matching components of two grids of the same shape. Panics if the
+    /// shapes differ.
+    fn zip_components(&self, other: &Self, f: &dyn Fn(&SymbolicExpr, &SymbolicExpr) -> SymbolicExpr) -> Self;
+    
+    /// Contracts index `slot` with a matrix, `T'_..a.. = M_ab T_..b..`. `slot` must be
+    /// less than the rank, and the matrix square with one row per value of that index;
+    /// panics otherwise.
+    fn contract_slot(&self, slot: usize, matrix: &MetricTensor) -> Self;
+    
+    /// Whether every index runs over exactly `n` values.
+    fn has_dimension(&self, n: usize) -> bool;
+}
+
+impl ComponentGrid for SymbolicExpr {
+    fn rank() -> usize {
+        0
+    }
+    
+    fn map_components(&self, f: &dyn Fn(&SymbolicExpr) -> SymbolicExpr) -> Self {
+        f(self)
+    }
+    
+    fn zip_components(&self, other: &Self, f: &dyn Fn(&SymbolicExpr, &SymbolicExpr) -> SymbolicExpr) -> Self {
+        f(self, other)
+    }
+    
+    fn contract_slot(&self, _slot: usize, _matrix: &MetricTensor) -> Self {
+        self.clone()
+    }
+    
+    fn has_dimension(&self, _n: usize) -> bool {
+        true
+    }
+}
+
+impl<T: ComponentGrid> ComponentGrid for Vec<T> {
+    fn rank() -> usize {
+        T::rank() + 1
+    }
+    
+    fn map_components(&self, f: &dyn Fn(&SymbolicExpr) -> SymbolicExpr) -> Self {
+        self.iter().map(|item| item.map_components(f)).collect()
+    }
+    
+    fn zip_components(&self, other: &Self, f: &dyn Fn(&SymbolicExpr, &SymbolicExpr) -> SymbolicExpr) -> Self {
+        assert_eq!(self.len(), other.len(), "zip_components needs grids of the same shape");
+        self.iter().zip(other).map(|(a, b)| a.zip_components(b, f)).collect()
+    }
     
-    matrix
-}
\ No newline at end of file
+    fn contract_slot(&self, slot: usize, matrix: &MetricTensor) -> Self {
+        if slot > 0 {
+            return self.iter().map(|item| item.contract_slot(slot - 1, matrix)).collect();
+        }
+        assert!(
+            matrix.len() == self.len() && matrix.iter().all(|row| row.len() == self.len()),
+            "contract_slot needs a {0}x{0} matrix", self.len()
+        );
+        
+        let zero = |_: &SymbolicExpr| SymbolicExpr::Zero;
+        (0..self.len())
+            .map(|a| {
+                let mut sum = self[0].map_components(&zero);
+                for (lambda, item) in self.iter().enumerate() {
+                    let factor = &matrix[a][lambda];
+                    if factor.is_zero() {
+                        continue;
+                    }
+                    sum = sum.zip_components(item, &|acc, component| {
+                        if component.is_zero() {
+                            acc.clone()
+                        } else {
+                            SymbolicExpr::Add(
+                                Box::new(acc.clone()),
+                                Box::new(SymbolicExpr::Multiply(Box::new(factor.clone()), Box::new(component.clone()))),
+                            )
+                        }
+                    });
+                }
+                sum
+            })
+            .collect()
+    }
+    
+    fn has_dimension(&self, n: usize) -> bool {
+        self.len() == n && self.iter().all(|item| item.has_dimension(n))
+    }
+}
+
+/// Lowers index `slot` of `tensor` with `g_μν`.
+pub fn lower_index<T: ComponentGrid>(tensor: &T, slot: usize, metric: &MetricTensor) -> Result<T, TensorError> {
+    contract_index(tensor, slot, metric)
+}
+
+/// Raises index `slot` of `tensor` with `g^μν`.
+pub fn raise_index<T: ComponentGrid>(tensor: &T, slot: usize, metric: &MetricTensor) -> Result<T, TensorError> {
+    contract_index(tensor, slot, &calculate_metric_inverse(metric)?)
+}
+
+fn contract_index<T: ComponentGrid>(tensor: &T, slot: usize, matrix: &MetricTensor) -> Result<T, TensorError> {
+    if slot >= T::rank() {
+        return Err(TensorError::ComputationError(format!(
+            "Index {} is out of range for a rank-{} tensor", slot, T::rank()
+        )));
+    }
+    let n = matrix.len();
+    if matrix.iter().any(|row| row.len() != n) || !tensor.has_dimension(n) {
+        return Err(TensorError::ComputationError(format!(
+            "Every index of the tensor must run over the {} dimensions of the metric", n
+        )));
+    }
+    Ok(tensor.contract_slot(slot, matrix).map_components(&|expr| expr.simplify()))
+}
+
+/// `∇_k T_ij = ∂_k T_ij - Γ^l_ki T_lj - Γ^l_kj T_il`, indexed `[k][i][j]`.
+fn covariant_derivative_rank2(
+    tensor: &MetricTensor,
+    christoffel: &ChristoffelSymbols,
+    coords: &[String],
+) -> Vec<Vec<Vec<SymbolicExpr>>> {
+    let n = tensor.len();
+    let mut result = vec![vec![vec![SymbolicExpr::Zero; n]; n]; n];
+    
+    for k in 0..n {
+        for i in 0..n {
+            for j in 0..n {
+                let mut expr = tensor[i][j].derivative(&coords[k]);
+                for l in 0..n {
+                    expr = SymbolicExpr::Subtract(
+                        Box::new(expr),
+                        Box::new(SymbolicExpr::Add(
+                            Box::new(SymbolicExpr::Multiply(
+                                Box::new(christoffel[l][k][i].clone()),
+                                Box::new(tensor[l][j].clone()),
+                            )),
+                            Box::new(SymbolicExpr::Multiply(
+                                Box::new(christoffel[l][k][j].clone()),
+                                Box::new(tensor[i][l].clone()),
+                            )),
+                        )),
+                    );
+                }
+                result[k][i][j] = expr.simplify();
+            }
+        }
+    }
+    
+    result
+}
+
+/// Components of a rank-2 tensor, skipping zeros unless `include_zeros` is set.
+pub(crate) fn rank2_components(tensor: &MetricTensor, include_zeros: bool) -> Vec<TensorComponent> {
+    let mut components = Vec::new();
+    for (i, row) in tensor.iter().enumerate() {
+        for (j, expr) in row.iter().enumerate() {
+            if include_zeros || !expr.is_zero() {
+                components.push(TensorComponent { indices: vec![i, j], expression: expr.to_string() });
+            }
+        }
+    }
+    components
+}
+
+fn rank3_components(tensor: &ChristoffelSymbols, include_zeros: bool) -> Vec<TensorComponent> {
+    let mut components = Vec::new();
+    for (i, matrix) in tensor.iter().enumerate() {
+        for mut component in rank2_components(matrix, include_zeros) {
+            component.indices.insert(0, i);
+            components.push(component);
+        }
+    }
+    components
+}
+
+fn rank4_components(tensor: &RiemannTensor, include_zeros: bool) -> Vec<TensorComponent> {
+    let mut components = Vec::new();
+    for (i, slice) in tensor.iter().enumerate() {
+        for mut component in rank3_components(slice, include_zeros) {
+            component.indices.insert(0, i);
+            components.push(component);
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::DomainRestriction;
+    use std::collections::HashMap;
+
+    fn strings(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|s| s.to_string()).collect()).collect()
+    }
+
+    /// Schwarzschild and its coordinates `(t, r, theta, phi)`.
+    fn schwarzschild() -> (MetricTensor, Vec<String>) {
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[
+            &["-(1 - 2*M/r)", "0", "0", "0"],
+            &["0", "1/(1 - 2*M/r)", "0", "0"],
+            &["0", "0", "r^2", "0"],
+            &["0", "0", "0", "r^2 * sin(theta)^2"],
+        ]), &coords).unwrap();
+        (metric, coords)
+    }
+
+    /// Spatially flat FLRW with scale factor `a(t)`, and its coordinates `(t, r, theta, phi)`.
+    fn flrw() -> (MetricTensor, Vec<String>) {
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "0"],
+            &["0", "a(t)^2", "0", "0"],
+            &["0", "0", "a(t)^2 * r^2", "0"],
+            &["0", "0", "0", "a(t)^2 * r^2 * sin(theta)^2"],
+        ]), &coords).unwrap();
+        (metric, coords)
+    }
+
+    #[test]
+    fn test_format_indexed() {
+        let christoffel = TensorComponent { indices: vec![1, 2, 2], expression: "-r".to_string() };
+        assert_eq!(christoffel.format_indexed("Γ", &[true, false, false]), "Γ^{1}_{22} = -r");
+        
+        let riemann = TensorComponent { indices: vec![0, 1, 0, 1], expression: "2*M/r^3".to_string() };
+        assert_eq!(riemann.format_indexed("R", &[true, false, false, false]), "R^{0}_{101} = 2*M/r^3");
+        
+        let ricci = TensorComponent { indices: vec![10, 3], expression: "0".to_string() };
+        assert_eq!(ricci.format_indexed("R", &[]), "R_{10,3} = 0");
+    }
+
+    #[test]
+    fn test_asymmetric_metric_rejected() {
+        let coords = vec!["x".to_string(), "y".to_string()];
+        let metric = parse_metric_tensor(strings(&[&["1", "x"], &["y", "1"]]), &coords).unwrap();
+
+        match calculate_christoffel_symbols(&metric, &coords) {
+            Err(TensorError::InvalidMetric(msg)) => assert!(msg.contains("symmetric")),
+            other => panic!("Expected InvalidMetric error, got {:?}", other),
+        }
+        assert!(calculate_einstein_tensor(&metric, &coords).is_err());
+
+        let options = CurvatureOptions { allow_asymmetric: true, ..Default::default() };
+        assert!(calculate_christoffel_symbols_with_options(&metric, &coords, &options).is_ok());
+    }
+
+    #[test]
+    fn test_minkowski_riemann_vanishes() {
+        let cartesian: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "1"],
+        ]), &cartesian).unwrap();
+        assert!(calculate_riemann_tensor(&metric, &cartesian).unwrap().components.is_empty());
+
+        // The Christoffel symbols don't vanish in spherical coordinates, but their
+        // contributions to the curvature have to cancel exactly
+        let spherical: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "r^2", "0"],
+            &["0", "0", "0", "r^2 * sin(theta)^2"],
+        ]), &spherical).unwrap();
+        assert!(!calculate_christoffel_symbols(&metric, &spherical).unwrap().symbols.is_empty());
+        assert!(calculate_riemann_tensor(&metric, &spherical).unwrap().components.is_empty());
+    }
+
+    #[test]
+    fn test_small_coefficients_kept() {
+        // dr^2 + (1e-7 r^2)^2 dtheta^2 is curved whatever the scale of the second
+        // entry, so tiny coefficients must not be mistaken for cancellation
+        let coords: Vec<String> = ["r", "theta"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[&["1", "0"], &["0", "1e-14 * r^4"]]), &coords).unwrap();
+        let symbols = calculate_christoffel_symbols(&metric, &coords).unwrap();
+        assert!(symbols.symbols.iter().any(|c| c.indices == vec![0, 1, 1]));
+        assert!(!calculate_riemann_tensor(&metric, &coords).unwrap().components.is_empty());
+        let scalar = calculate_ricci_scalar(&metric, &coords).unwrap();
+        crate::assert_expr_eq!(SymbolicExpr::parse(&scalar.expression).unwrap(), SymbolicExpr::parse("-4 / r^2").unwrap());
+    }
+
+    #[test]
+    fn test_flrw_christoffel_symbols() {
+        let (metric, coords) = flrw();
+        let symbols = calculate_christoffel_symbols(&metric, &coords).unwrap();
+        let symbol = |indices: [usize; 3]| {
+            let component = symbols.symbols.iter().find(|c| c.indices == indices).unwrap();
+            SymbolicExpr::parse(&component.expression).unwrap().expand()
+        };
+
+        // Γ^t_rr = a a' and Γ^r_tr = a'/a
+        assert_eq!(symbol([0, 1, 1]), SymbolicExpr::parse("a(t) * a'(t)").unwrap().expand());
+        assert_eq!(symbol([1, 0, 1]), SymbolicExpr::parse("a'(t) / a(t)").unwrap().expand());
+        assert_eq!(symbol([0, 2, 2]), SymbolicExpr::parse("a(t) * a'(t) * r^2").unwrap().expand());
+        assert!(symbols.symbols.iter().all(|c| c.indices != vec![0, 0, 0]));
+    }
+
+    #[test]
+    fn test_weyl_tensor() {
+        // FLRW is conformally flat
+        let (flrw, coords) = flrw();
+        let weyl = calculate_weyl_tensor(&flrw, &coords).unwrap();
+        assert!(weyl.components.is_empty(), "{:?}", weyl.components);
+        
+        let (schwarzschild, _) = schwarzschild();
+        let weyl = calculate_weyl_tensor(&schwarzschild, &coords).unwrap();
+        assert!(!weyl.components.is_empty());
+        
+        let plane = parse_metric_tensor(strings(&[&["1", "0"], &["0", "1"]]), &coords[..2]).unwrap();
+        assert!(calculate_weyl_tensor(&plane, &coords[..2]).is_err());
+    }
+
+    #[test]
+    fn test_mixed_einstein_tensor() {
+        let (flrw, coords) = flrw();
+        let mixed = calculate_einstein_tensor_mixed(&flrw, &coords).unwrap();
+        let component = |indices: [usize; 2]| {
+            let component = mixed.components.iter().find(|c| c.indices == indices).unwrap();
+            SymbolicExpr::parse(&component.expression).unwrap()
+        };
+        
+        // G^t_t = -3 (a'/a)^2 and G^r_r = -(2 a''/a + (a'/a)^2)
+        assert_eq!(component([0, 0]).expand(), SymbolicExpr::parse("-3 * a'(t)^2 / a(t)^2").unwrap().expand());
+        assert_eq!(
+            component([1, 1]).expand(),
+            SymbolicExpr::parse("-(2 * a''(t) / a(t) + a'(t)^2 / a(t)^2)").unwrap().expand()
+        );
+        crate::assert_expr_eq!(component([1, 1]), component([3, 3]));
+        assert!(mixed.components.iter().all(|c| c.indices[0] == c.indices[1]));
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_raise_and_lower_index() {
+        let (metric, coords) = schwarzschild();
+        
+        // Raising one index of g_μν gives δ^μ_ν, and lowering it again gives back g_μν
+        let delta = raise_index(&metric, 0, &metric).unwrap();
+        for mu in 0..4 {
+            for nu in 0..4 {
+                let expected = if mu == nu { SymbolicExpr::One } else { SymbolicExpr::Zero };
+                crate::assert_expr_eq!(delta[mu][nu], expected);
+            }
+        }
+        let lowered = lower_index(&delta, 0, &metric).unwrap();
+        for mu in 0..4 {
+            for nu in 0..4 {
+                crate::assert_expr_eq!(lowered[mu][nu], metric[mu][nu]);
+            }
+        }
+        
+        // Works on any rank, here a vector
+        let velocity = vec![SymbolicExpr::One, SymbolicExpr::Zero, SymbolicExpr::Zero, SymbolicExpr::Zero];
+        crate::assert_expr_eq!(lower_index(&velocity, 0, &metric).unwrap()[0], metric[0][0]);
+        assert!(lower_index(&velocity, 1, &metric).is_err());
+        
+        // Components have to match the dimension of the metric
+        let polar = parse_metric_tensor(strings(&[&["1", "0"], &["0", "r^2"]]), &coords[1..3]).unwrap();
+        let one = SymbolicExpr::One;
+        for tensor in [vec![one.clone(), one.clone(), one.clone()], Vec::new()] {
+            match lower_index(&tensor, 0, &polar) {
+                Err(TensorError::ComputationError(_)) => {}
+                other => panic!("Expected ComputationError, got {:?}", other),
+            }
+        }
+        assert!(raise_index(&vec![vec![one.clone(); 2], vec![one.clone()]], 1, &polar).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_covariant_derivative_vector() {
+        let cartesian = vec!["x".to_string(), "y".to_string()];
+        let plane = parse_metric_tensor(strings(&[&["1", "0"], &["0", "1"]]), &cartesian).unwrap();
+        let constant = vec![SymbolicExpr::One, SymbolicExpr::Constant(2.0)];
+        let gradient = covariant_derivative_vector(&constant, &plane, &cartesian).unwrap();
+        assert!(gradient.iter().flatten().all(|c| c.is_zero()));
+        
+        // The unit radial field ∂_r in flat spherical coordinates spreads out:
+        // ∇_θ V^θ = ∇_φ V^φ = 1/r through Γ^θ_θr and Γ^φ_φr
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let flat = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "r^2", "0"],
+            &["0", "0", "0", "r^2 * sin(theta)^2"],
+        ]), &coords).unwrap();
+        let radial = vec![SymbolicExpr::Zero, SymbolicExpr::One, SymbolicExpr::Zero, SymbolicExpr::Zero];
+        let gradient = covariant_derivative_vector(&radial, &flat, &coords).unwrap();
+        let inverse_r = SymbolicExpr::parse("1/r").unwrap();
+        crate::assert_expr_eq!(gradient[2][2], inverse_r);
+        crate::assert_expr_eq!(gradient[3][3], inverse_r);
+        for mu in 0..4 {
+            for nu in 0..4 {
+                if !(mu == nu && mu >= 2) {
+                    assert!(gradient[mu][nu].is_zero(), "∇_{} V^{} = {}", mu, nu, gradient[mu][nu]);
+                }
+            }
+        }
+        
+        assert!(covariant_derivative_vector(&constant, &flat, &coords).is_err());
+    }
+
+    #[test]
+    fn test_christoffel_symbols_with_inverse() {
+        let (schwarzschild, coords) = schwarzschild();
+        let inverse = parse_metric_tensor(strings(&[
+            &["-r/(r - 2*M)", "0", "0", "0"],
+            &["0", "(r - 2*M)/r", "0", "0"],
+            &["0", "0", "1/r^2", "0"],
+            &["0", "0", "0", "1/(r^2 * sin(theta)^2)"],
+        ]), &coords).unwrap();
+        
+        let expected = calculate_christoffel_symbols(&schwarzschild, &coords).unwrap().symbols;
+        let actual = calculate_christoffel_symbols_with_inverse(&schwarzschild, &inverse, &coords, &CurvatureOptions::default()).unwrap().symbols;
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(&expected) {
+            assert_eq!(a.indices, e.indices);
+            crate::assert_expr_eq!(SymbolicExpr::parse(&a.expression).unwrap(), SymbolicExpr::parse(&e.expression).unwrap());
+        }
+        
+        assert!(calculate_christoffel_symbols_with_inverse(&schwarzschild, &inverse[..2].to_vec(), &coords, &CurvatureOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_tensor_trace() {
+        let (schwarzschild, _) = schwarzschild();
+        crate::assert_expr_eq!(tensor_trace(&schwarzschild, &schwarzschild).unwrap(), SymbolicExpr::Constant(4.0));
+        
+        let sphere_coords = vec!["theta".to_string(), "phi".to_string()];
+        let sphere = parse_metric_tensor(strings(&[&["R^2", "0"], &["0", "R^2 * sin(theta)^2"]]), &sphere_coords).unwrap();
+        let ricci = calculate_ricci_tensor_full(&sphere, &sphere_coords).unwrap();
+        let scalar = SymbolicExpr::parse(&calculate_ricci_scalar(&sphere, &sphere_coords).unwrap().expression).unwrap();
+        crate::assert_expr_eq!(tensor_trace(&ricci, &sphere).unwrap(), scalar);
+        crate::assert_expr_eq!(scalar, SymbolicExpr::parse("2/R^2").unwrap());
+        
+        assert!(tensor_trace(&ricci, &schwarzschild).is_err());
+    }
+
+    #[test]
+    fn test_line_element() {
+        let (schwarzschild, coords) = schwarzschild();
+        
+        // Radial infall: only t and r change
+        let radial = [
+            SymbolicExpr::Variable("tdot".to_string()),
+            SymbolicExpr::Variable("rdot".to_string()),
+            SymbolicExpr::Zero,
+            SymbolicExpr::Zero,
+        ];
+        let lagrangian = line_element(&schwarzschild, &coords, &radial).unwrap();
+        crate::assert_expr_eq!(lagrangian, SymbolicExpr::parse("-(1 - 2*M/r)*tdot^2 + rdot^2/(1 - 2*M/r)").unwrap());
+        
+        let off_diagonal = parse_metric_tensor(strings(&[&["0", "1"], &["1", "0"]]), &coords[..2]).unwrap();
+        let velocities = [SymbolicExpr::Variable("u".to_string()), SymbolicExpr::Variable("v".to_string())];
+        crate::assert_expr_eq!(line_element(&off_diagonal, &coords[..2], &velocities).unwrap(), SymbolicExpr::parse("2*u*v").unwrap());
+        
+        assert!(line_element(&schwarzschild, &coords, &velocities).is_err());
+    }
+
+    #[test]
+    fn test_geodesic_equations() {
+        let (metric, coords) = schwarzschild();
+        let equations = geodesic_equations(&metric, &coords).unwrap();
+        assert_eq!(equations.len(), 4);
+        
+        // t'' + 2 Γ^t_tr t' r' = 0
+        let gamma = calculate_christoffel_symbols_full(&metric, &coords).unwrap();
+        let expected = SymbolicExpr::parse(&format!("xddot(t) + 2 * ({}) * xdot(t) * xdot(r)", gamma[0][0][1])).unwrap();
+        let time = SymbolicExpr::parse(&equations[0].expression).unwrap();
+        assert_eq!(time.expand(), expected.expand());
+        assert!(equations[2].expression.contains("xdot(phi)"));
+    }
+
+    #[test]
+    fn test_include_zeros() {
+        let coords = vec!["r".to_string(), "theta".to_string()];
+        let metric = parse_metric_tensor(strings(&[&["1", "0"], &["0", "r^2"]]), &coords).unwrap();
+
+        let sparse = calculate_christoffel_symbols(&metric, &coords).unwrap();
+        assert_eq!(sparse.symbols.len(), 3);
+
+        let options = CurvatureOptions { include_zeros: true, ..Default::default() };
+        let dense = calculate_christoffel_symbols_with_options(&metric, &coords, &options).unwrap();
+        assert_eq!(dense.symbols.len(), 8);
+        for mu in 0..2 {
+            for alpha in 0..2 {
+                for beta in 0..2 {
+                    assert!(dense.symbols.iter().any(|c| c.indices == vec![mu, alpha, beta]));
+                }
+            }
+        }
+        let zero = dense.symbols.iter().find(|c| c.indices == vec![0, 0, 0]).unwrap();
+        assert_eq!(zero.expression, "0");
+
+        let ricci = calculate_ricci_tensor_with_options(&metric, &coords, &options).unwrap();
+        assert_eq!(ricci.components.len(), 4);
+    }
+
+    #[test]
+    fn test_computation_budget() {
+        let coords = vec!["r".to_string(), "theta".to_string()];
+        let budget_error = |result: Result<RiemannResult, TensorError>| match result {
+            Err(TensorError::ComputationError(message)) => message.contains("budget exceeded"),
+            _ => false,
+        };
+
+        // A balanced sum of 2^18 copies of r, far beyond the default node limit
+        let mut huge = SymbolicExpr::Variable("r".to_string());
+        for _ in 0..18 {
+            huge = SymbolicExpr::Add(Box::new(huge.clone()), Box::new(huge));
+        }
+        let metric = vec![
+            vec![huge, SymbolicExpr::Zero],
+            vec![SymbolicExpr::Zero, SymbolicExpr::Variable("r".to_string())],
+        ];
+        assert!(budget_error(calculate_riemann_tensor(&metric, &coords)));
+
+        let metric = parse_metric_tensor(strings(&[&["1", "0"], &["0", "r^2 * sin(theta)^2"]]), &coords).unwrap();
+        assert!(calculate_riemann_tensor(&metric, &coords).is_ok());
+        let tight = |max_nodes, max_components| CurvatureOptions {
+            budget: ComputationBudget { max_nodes, max_components },
+            ..Default::default()
+        };
+        assert!(budget_error(calculate_riemann_tensor_with_options(&metric, &coords, &tight(5, 100))));
+        assert!(budget_error(calculate_riemann_tensor_with_options(&metric, &coords, &tight(1000, 15))));
+        // The component count is checked before any Christoffel symbols are computed
+        match calculate_riemann_tensor_with_options(&metric, &coords, &tight(5, 15)) {
+            Err(TensorError::ComputationError(message)) => assert!(message.contains("16 components"), "{}", message),
+            other => panic!("Expected a budget error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_schwarzschild_tidal_tensor() {
+        let (metric, coords) = schwarzschild();
+        let static_observer = vec![SymbolicExpr::One, SymbolicExpr::Zero, SymbolicExpr::Zero, SymbolicExpr::Zero];
+
+        let tidal = tidal_tensor(&metric, &coords, &static_observer).unwrap();
+
+        let (m, r) = (1.0, 10.0);
+        let bindings: HashMap<String, f64> = [("M", m), ("r", r), ("theta", 1.0)]
+            .iter().map(|(k, v)| (k.to_string(), *v)).collect();
+
+        // Radial stretching: E_rr = -2M/r^3 * g_rr
+        let e_rr = tidal[1][1].eval(&bindings).unwrap();
+        assert!((e_rr - (-2.0 * m / r.powi(3)) / (1.0 - 2.0 * m / r)).abs() < 1e-9);
+
+        // Transverse compression: E_θθ = M/r^3 * g_θθ
+        let e_thth = tidal[2][2].eval(&bindings).unwrap();
+        assert!((e_thth - m / r).abs() < 1e-9);
+
+        assert!(tidal[0][0].eval(&bindings).unwrap().abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_schwarzschild_null_expansion() {
+        let (metric, coords) = schwarzschild();
+        // Outgoing radial null vector scaled by (1 - 2M/r), giving θ = 2(r - 2M)/r^2
+        let f = SymbolicExpr::parse("1 - 2*M/r").unwrap();
+        let outgoing = vec![
+            f.clone(),
+            SymbolicExpr::Multiply(Box::new(f.clone()), Box::new(f)),
+            SymbolicExpr::Zero,
+            SymbolicExpr::Zero,
+        ];
+
+        let theta = null_expansion(&metric, &coords, &outgoing).unwrap();
+
+        // The raw expression still contains 1/(1 - 2M/r) factors, so compare against the
+        // closed form rather than evaluating at the horizon itself
+        crate::assert_expr_eq!(theta, SymbolicExpr::parse("2*(r - 2*M)/r^2").unwrap());
+        let bindings: HashMap<String, f64> = [("M", 1.0), ("r", 2.0 + 1e-9), ("theta", 1.0)]
+            .iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        assert!(theta.eval(&bindings).unwrap().abs() < 1e-8);
+
+        let timelike = vec![SymbolicExpr::One, SymbolicExpr::Zero, SymbolicExpr::Zero, SymbolicExpr::Zero];
+        assert!(null_expansion(&metric, &coords, &timelike).is_err());
+    }
+
+    #[test]
+    fn test_diff_tensors() {
+        let (metric, coords) = schwarzschild();
+        let ricci = calculate_ricci_tensor(&metric, &coords).unwrap();
+        
+        assert!(diff_tensors(&ricci, &ricci).is_empty());
+        
+        // A hand-computed result with one wrong component
+        let mut edited = ricci.clone();
+        edited.components.retain(|c| c.indices != vec![2, 2]);
+        edited.components.push(TensorComponent { indices: vec![2, 2], expression: "2*M/r".to_string() });
+        
+        let diff = diff_tensors(&ricci, &edited);
+        assert_eq!(diff.differences.len(), 1);
+        assert_eq!(diff.differences[0].indices, vec![2, 2]);
+        assert!(SymbolicExpr::parse(&diff.differences[0].after).unwrap()
+            .approx_eq(&SymbolicExpr::parse("2*M/r").unwrap()));
+    }
+
+    #[test]
+    fn test_common_subexpression_elimination() {
+        let (metric, coords) = schwarzschild();
+        let christoffel = calculate_christoffel_symbols(&metric, &coords).unwrap();
+        
+        let cse = eliminate_common_subexpressions(&christoffel.symbols).unwrap();
+        
+        let node_count = |components: &[TensorComponent]| -> usize {
+            components.iter().map(|c| SymbolicExpr::parse(&c.expression).unwrap().node_count()).sum()
+        };
+        let compressed = node_count(&cse.components)
+            + cse.bindings.iter().map(|b| SymbolicExpr::parse(&b.expression).unwrap().node_count()).sum::<usize>();
+        assert!(!cse.bindings.is_empty());
+        assert!(compressed < node_count(&christoffel.symbols));
+        
+        let expanded = cse.expand().unwrap();
+        for (original, expanded) in christoffel.symbols.iter().zip(&expanded) {
+            assert_eq!(original.indices, expanded.indices);
+            assert_eq!(SymbolicExpr::parse(&original.expression).unwrap(), SymbolicExpr::parse(&expanded.expression).unwrap());
+        }
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn assert_inverse(metric: &MetricTensor, inverse: &MetricTensor) {
+        let n = metric.len();
+        for i in 0..n {
+            for j in 0..n {
+                let mut product = SymbolicExpr::Zero;
+                for k in 0..n {
+                    product = SymbolicExpr::Add(
+                        Box::new(product),
+                        Box::new(SymbolicExpr::Multiply(Box::new(metric[i][k].clone()), Box::new(inverse[k][j].clone()))),
+                    );
+                }
+                let expected = if i == j { SymbolicExpr::One } else { SymbolicExpr::Zero };
+                crate::assert_expr_eq!(product.simplify(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_metric_inverse() {
+        // The 2x2 closed form agrees with the general adjugate path
+        let metric = parse_metric_tensor(strings(&[&["x^2", "x*y"], &["x*y", "1 + y^2"]]), &["x".to_string(), "y".to_string()]).unwrap();
+        let closed = calculate_metric_inverse(&metric).unwrap();
+        let general = adjugate_inverse(&metric).unwrap();
+        for i in 0..2 {
+            for j in 0..2 {
+                crate::assert_expr_eq!(closed[i][j], general[i][j]);
+            }
+        }
+        assert_inverse(&metric, &closed);
+
+        let (schwarzschild, coords) = schwarzschild();
+        assert_inverse(&schwarzschild, &calculate_metric_inverse(&schwarzschild).unwrap());
+
+        let kerr = &crate::einstein::solve_vacuum_einstein_equations(&coords, "axisymmetric", &[]).unwrap()[0].metric_tensor;
+        let inverse = calculate_metric_inverse(kerr).unwrap();
+        assert!(!inverse[0][3].is_zero());
+        assert_inverse(kerr, &inverse);
+
+        let singular = parse_metric_tensor(strings(&[&["1", "1"], &["1", "1"]]), &coords[..2]).unwrap();
+        assert!(calculate_metric_inverse(&singular).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_diagonal_metric_inverse() {
+        let (flrw, coords) = flrw();
+        assert!(is_diagonal(&flrw));
+        let inverse = calculate_metric_inverse(&flrw).unwrap();
+        for i in 0..4 {
+            let expected = SymbolicExpr::Divide(Box::new(SymbolicExpr::One), Box::new(flrw[i][i].clone())).simplify();
+            assert_eq!(inverse[i][i], expected);
+            for j in 0..4 {
+                if i != j {
+                    assert_eq!(inverse[i][j], SymbolicExpr::Zero);
+                }
+            }
+        }
+        assert_eq!(inverse[0][0], SymbolicExpr::Constant(-1.0));
+        assert_inverse(&flrw, &inverse);
+
+        // An off-diagonal metric goes through the adjugate
+        let rotated = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "w"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "r^2", "0"],
+            &["w", "0", "0", "r^2"],
+        ]), &coords).unwrap();
+        assert!(!is_diagonal(&rotated));
+        assert_eq!(calculate_metric_inverse(&rotated).unwrap(), adjugate_inverse(&rotated).unwrap());
+    }
+
+    #[test]
+    fn test_parse_metric_rejects_malformed_entries() {
+        let coords = vec!["r".to_string(), "theta".to_string()];
+        match parse_metric_tensor(strings(&[&["1", "0"], &["0", "r^2 *"]]), &coords) {
+            Err(TensorError::ParseError(message)) => assert!(message.contains("(1, 1)"), "{}", message),
+            other => panic!("Expected ParseError, got {:?}", other),
+        }
+        assert!(matches!(
+            parse_metric_tensor(strings(&[&["", "0"], &["0", "r^2"]]), &coords),
+            Err(TensorError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_diagonal_metric() {
+        let (full, coords) = schwarzschild();
+        let diagonal = ["-(1 - 2*M/r)", "1/(1 - 2*M/r)", "r^2", "r^2 * sin(theta)^2"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(parse_diagonal_metric(diagonal, &coords).unwrap(), full);
+        
+        assert!(parse_diagonal_metric(vec!["1".to_string()], &coords).is_err());
+    }
+
+    #[test]
+    fn test_riemann_components_iter() {
+        let (metric, coords) = schwarzschild();
+        
+        let options = CurvatureOptions::default();
+        let key = |c: &TensorComponent| (c.indices.clone(), c.expression.clone());
+        let mut streamed: Vec<_> = riemann_components_iter(&metric, &coords, &options)
+            .map(|component| key(&component.unwrap()))
+            .collect();
+        let mut expected: Vec<_> = calculate_riemann_tensor(&metric, &coords).unwrap()
+            .components.iter().map(key).collect();
+        streamed.sort();
+        expected.sort();
+        assert_eq!(streamed, expected);
+        
+        // Short-circuiting only needs the first slice
+        let first = riemann_components_iter(&metric, &coords, &options).next().unwrap().unwrap();
+        assert_eq!(first.indices[0], 0);
+        
+        let mut errors = riemann_components_iter(&metric, &coords[..2], &options);
+        assert!(errors.next().unwrap().is_err());
+        assert!(errors.next().is_none());
+        
+        let tight = CurvatureOptions { budget: ComputationBudget { max_components: 255, ..Default::default() }, ..options };
+        let mut errors = riemann_components_iter(&metric, &coords, &tight);
+        assert!(matches!(errors.next(), Some(Err(TensorError::ComputationError(_)))));
+    }
+
+    #[test]
+    fn test_typed_component_round_trip() {
+        let coords = vec!["t".to_string(), "r".to_string()];
+        let metric = parse_metric_tensor(strings(&[
+            &["-(1 - 2*M/r)", "0"],
+            &["0", "1/(1 - 2*M/r)"],
+        ]), &coords).unwrap();
+        let symbols = calculate_christoffel_symbols(&metric, &coords).unwrap();
+        let component = &symbols.symbols[0];
+        
+        let typed = component.typed().unwrap();
+        assert_eq!(typed.expression, component.expression);
+        assert_eq!(typed.expression_tree, SymbolicExpr::parse(&component.expression).unwrap());
+        
+        let json = serde_json::to_value(&typed).unwrap();
+        assert!(json["expression_tree"].is_object());
+        let decoded: TensorComponentTyped = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, typed);
+        
+        let untyped: TensorComponent = decoded.into();
+        assert_eq!(untyped.indices, component.indices);
+        assert_eq!(untyped.expression, component.expression);
+    }
+
+    #[test]
+    fn test_parse_metric_from_sympy() {
+        let (expected, coords) = schwarzschild();
+        
+        let sympy = "Matrix([[-(1 - 2*M/r), 0, 0, 0], [0, 1/(1 - 2*M/r), 0, 0],\n    [0, 0, r**2, 0], [0, 0, 0, r**2*sin(theta)**2]])";
+        assert_eq!(parse_metric_from_sympy(sympy, &coords).unwrap(), expected);
+        
+        let mathematica = "{{-(1 - 2*M/r), 0, 0, 0}, {0, 1/(1 - 2*M/r), 0, 0}, {0, 0, r^2, 0}, {0, 0, 0, r^2*Sin[theta]^2}}";
+        assert_eq!(parse_metric_from_sympy(mathematica, &coords).unwrap(), expected);
+        
+        // Commas inside function arguments don't split entries
+        let pp_wave = parse_metric_from_sympy("[[H(u, x), -1/2], [-1/2, 0]]", &coords[..2]).unwrap();
+        assert_eq!(pp_wave[0][0], SymbolicExpr::parse("H(u, x)").unwrap());
+        
+        assert!(parse_metric_from_sympy("[[1, 0], [0]]", &coords[..2]).is_err());
+        assert!(parse_metric_from_sympy("1, 0", &coords[..2]).is_err());
+    }
+
+    #[test]
+    fn test_metric_determinant() {
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let polar = parse_metric_tensor(strings(&[&["1", "0"], &["0", "r^2"]]), &coords[1..3]).unwrap();
+        crate::assert_expr_eq!(calculate_metric_determinant(&polar).unwrap(), SymbolicExpr::parse("r^2").unwrap());
+
+        let (schwarzschild, _) = schwarzschild();
+        let det = calculate_metric_determinant(&schwarzschild).unwrap();
+        crate::assert_expr_eq!(det, SymbolicExpr::parse("-r^4 * sin(theta)^2").unwrap());
+
+        // Boyer-Lindquist Kerr: g = -(r^2 + a^2 cos^2 theta)^2 sin^2 theta
+        let kerr = &crate::einstein::solve_vacuum_einstein_equations(&coords, "axisymmetric", &[]).unwrap()[0].metric_tensor;
+        let det = calculate_metric_determinant(kerr).unwrap();
+        crate::assert_expr_eq!(det, SymbolicExpr::parse("-(r^2 + a^2*cos(theta)^2)^2 * sin(theta)^2").unwrap());
+
+        let ragged = vec![vec![SymbolicExpr::One, SymbolicExpr::Zero], vec![SymbolicExpr::One]];
+        assert!(calculate_metric_determinant(&ragged).is_err());
+    }
+
+    #[test]
+    fn test_find_metric_singularities() {
+        let (schwarzschild, coords) = schwarzschild();
+        let singularities = find_metric_singularities(&schwarzschild, &coords).unwrap();
+        let conditions = |indices: &[usize]| -> Vec<String> {
+            singularities.iter().filter(|s| s.indices == indices).map(|s| s.expression.clone()).collect()
+        };
+        
+        // g_tt vanishes and g_rr blows up at the horizon
+        let horizon = format!("at r = {}", SymbolicExpr::parse("2*M").unwrap().simplify());
+        assert!(conditions(&[0, 0]).iter().any(|c| c.ends_with(&horizon)), "{:?}", singularities);
+        assert!(conditions(&[1, 1]).iter().any(|c| c.ends_with(&horizon)), "{:?}", singularities);
+        assert!(conditions(&[1, 1]).iter().any(|c| c.ends_with("diverges at r = 0")), "{:?}", singularities);
+        
+        // The angular part degenerates on the axis
+        assert!(conditions(&[3, 3]).contains(&"sin(theta) = 0".to_string()), "{:?}", singularities);
+        assert!(conditions(&[3, 3]).contains(&"r = 0 at r = 0".to_string()), "{:?}", singularities);
+        assert!(conditions(&[]).contains(&"sin(theta) = 0".to_string()), "{:?}", singularities);
+        
+        assert!(conditions(&[2, 2]).iter().all(|c| !c.contains("theta")));
+        
+        let flat = parse_metric_tensor(strings(&[&["-1", "0"], &["0", "1"]]), &coords[..2]).unwrap();
+        assert!(find_metric_singularities(&flat, &coords[..2]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_full_tensors_match_results() {
+        let coords = vec!["theta".to_string(), "phi".to_string()];
+        let sphere = parse_metric_tensor(strings(&[&["R^2", "0"], &["0", "R^2 * sin(theta)^2"]]), &coords).unwrap();
+
+        let christoffel = calculate_christoffel_symbols_full(&sphere, &coords).unwrap();
+        assert_eq!(christoffel.len(), 2);
+        assert!(christoffel[0][0][0].is_zero());
+        for component in calculate_christoffel_symbols(&sphere, &coords).unwrap().symbols {
+            let [i, j, k] = component.indices[..] else { panic!("expected three indices") };
+            assert_eq!(christoffel[i][j][k].to_string(), component.expression);
+        }
+
+        let riemann = calculate_riemann_tensor_full(&sphere, &coords).unwrap();
+        let components = calculate_riemann_tensor(&sphere, &coords).unwrap().components;
+        assert_eq!(components.len(), riemann.iter().flatten().flatten().flatten().filter(|c| !c.is_zero()).count());
+
+        let ricci = calculate_ricci_tensor_full(&sphere, &coords).unwrap();
+        crate::assert_expr_eq!(ricci[0][0], SymbolicExpr::One);
+        crate::assert_expr_eq!(ricci[1][1], SymbolicExpr::parse("sin(theta)^2").unwrap());
+
+        // The Einstein tensor vanishes identically in two dimensions
+        let einstein = calculate_einstein_tensor_full(&sphere, &coords).unwrap();
+        assert!(einstein.iter().flatten().all(|c| c.is_zero()));
+    }
+
+    #[test]
+    fn test_shared_christoffel_symbols() {
+        let (metric, coords) = schwarzschild();
+        
+        // Each quantity on its own recomputes the Christoffel symbols
+        let einstein = calculate_einstein_tensor_full(&metric, &coords).unwrap();
+        let kretschmann = calculate_kretschmann_scalar(&metric, &coords).unwrap();
+        
+        let budget = ComputationBudget::default();
+        let christoffel = calculate_christoffel_symbols_full(&metric, &coords).unwrap();
+        let shared_einstein = calculate_einstein_tensor_with_christoffel(&metric, &christoffel, &coords, &budget).unwrap();
+        let shared_kretschmann = calculate_kretschmann_scalar_with_christoffel(&metric, &christoffel, &coords, &budget).unwrap();
+        
+        assert_eq!(einstein, shared_einstein);
+        assert!(shared_einstein.iter().flatten().all(|c| c.is_zero()));
+        assert_eq!(kretschmann.expression, shared_kretschmann.expression);
+        
+        let riemann = calculate_riemann_tensor_with_christoffel(&christoffel, &coords, &budget).unwrap();
+        assert_eq!(riemann, calculate_riemann_tensor_full(&metric, &coords).unwrap());
+        crate::assert_expr_eq!(riemann[0][1][0][1], SymbolicExpr::parse("2*M / (r^2 * (r - 2*M))").unwrap());
+        crate::assert_expr_eq!(riemann[0][1][1][0], SymbolicExpr::parse("-2*M / (r^2 * (r - 2*M))").unwrap());
+        
+        assert!(calculate_riemann_tensor_with_christoffel(&christoffel, &coords[..2], &budget).is_err());
+        
+        let tight = ComputationBudget { max_nodes: 20, ..budget };
+        assert!(calculate_einstein_tensor_with_christoffel(&metric, &christoffel, &coords, &tight).is_err());
+        assert!(calculate_kretschmann_scalar_with_christoffel(&metric, &christoffel, &coords, &tight).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_riemann_matches_serial() {
+        let coords: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let metric = parse_metric_tensor(strings(&[
+            &["-(1 - 2*M/r + Q^2/r^2)", "0", "0", "0"],
+            &["0", "1/(1 - 2*M/r + Q^2/r^2)", "0", "0"],
+            &["0", "0", "r^2", "0"],
+            &["0", "0", "0", "r^2 * sin(theta)^2"],
+        ]), &coords).unwrap();
+        let christoffel = calculate_christoffel_symbols_full(&metric, &coords).unwrap();
+        
+        let derivatives = christoffel_gradients(&christoffel, &coords);
+        let budget = ComputationBudget::default();
+        let serial: RiemannTensor = (0..4)
+            .map(|rho| riemann_slice(rho, &christoffel, &derivatives, false, &budget).unwrap())
+            .collect();
+        let parallel = calculate_riemann_tensor_with_christoffel(&christoffel, &coords, &budget).unwrap();
+        
+        let serial_components = rank4_components(&serial, false);
+        let parallel_components = rank4_components(&parallel, false);
+        assert!(!parallel_components.is_empty());
+        assert_eq!(serial_components.len(), parallel_components.len());
+        for (a, b) in serial_components.iter().zip(&parallel_components) {
+            assert_eq!(a.indices, b.indices);
+            assert_eq!(a.expression, b.expression);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_riemann_symmetries_match_brute_force() {
+        let (metric, coords) = schwarzschild();
+        let christoffel = calculate_christoffel_symbols_full(&metric, &coords).unwrap();
+        
+        // Every R^ρ_σμν straight from the definition, with no symmetry shortcuts
+        let mut brute_force = Vec::new();
+        for rho in 0..4 {
+            for sigma in 0..4 {
+                for mu in 0..4 {
+                    for nu in 0..4 {
+                        let mut expr = SymbolicExpr::Subtract(
+                            Box::new(christoffel[rho][sigma][nu].derivative(&coords[mu])),
+                            Box::new(christoffel[rho][sigma][mu].derivative(&coords[nu])),
+                        );
+                        for lambda in 0..4 {
+                            expr = SymbolicExpr::Add(Box::new(expr), Box::new(SymbolicExpr::Subtract(
+                                Box::new(SymbolicExpr::Multiply(Box::new(christoffel[rho][lambda][mu].clone()), Box::new(christoffel[lambda][sigma][nu].clone()))),
+                                Box::new(SymbolicExpr::Multiply(Box::new(christoffel[rho][lambda][nu].clone()), Box::new(christoffel[lambda][sigma][mu].clone()))),
+                            )));
+                        }
+                        let expr = expr.simplify_expanded();
+                        if !expr.is_zero() {
+                            brute_force.push((vec![rho, sigma, mu, nu], expr));
+                        }
+                    }
+                }
+            }
+        }
+        
+        let components = calculate_riemann_tensor(&metric, &coords).unwrap().components;
+        assert_eq!(components.len(), brute_force.len());
+        for (component, (indices, expected)) in components.iter().zip(&brute_force) {
+            assert_eq!(&component.indices, indices);
+            crate::assert_expr_eq!(SymbolicExpr::parse(&component.expression).unwrap(), expected.clone());
+        }
+    }
+
+    #[test]
+    fn test_inverse_domain_restrictions() {
+        let (metric, coords) = schwarzschild();
+        let inverse = calculate_metric_inverse(&metric).unwrap();
+        
+        let horizon = |restrictions: &[DomainRestriction]| {
+            restrictions.iter()
+                .find(|r| r.variable == "r" && r.value.approx_eq(&SymbolicExpr::parse("2*M").unwrap()))
+                .cloned()
+        };
+        
+        // g^tt = -1/(1 - 2M/r) blows up at the horizon
+        let restriction = horizon(&inverse[0][0].domain_restrictions(&coords)).unwrap();
+        assert!(!restriction.removable);
+        
+        // g^rr = 1/(1/(1 - 2M/r)) cancels down to 1 - 2M/r, which is regular there
+        crate::assert_expr_eq!(inverse[1][1], SymbolicExpr::parse("1 - 2*M/r").unwrap());
+        assert!(horizon(&inverse[1][1].domain_restrictions(&coords)).is_none());
+        
+        // A factor the simplifier can't see is reported, but as removable
+        let g_rr = SymbolicExpr::parse("(r^2 - 4*M^2) / (r - 2*M)").unwrap();
+        let restriction = horizon(&g_rr.domain_restrictions(&coords)).unwrap();
+        assert!(restriction.removable);
+    }
+
+    #[test]
+    fn test_cotton_tensor() {
+        let coords: Vec<String> = ["x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        
+        // Conformally flat: Ω² δ_ij with Ω² = 1 + x^2 + y^2
+        let conformally_flat = parse_metric_tensor(strings(&[
+            &["1 + x^2 + y^2", "0", "0"],
+            &["0", "1 + x^2 + y^2", "0"],
+            &["0", "0", "1 + x^2 + y^2"],
+        ]), &coords).unwrap();
+        assert!(calculate_cotton_tensor(&conformally_flat, &coords).unwrap().components.is_empty());
+        
+        // The unit 3-sphere, written in curvature coordinates, is conformally flat too
+        let sphere_coords: Vec<String> = ["r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let sphere = parse_metric_tensor(strings(&[
+            &["1/(1 - r^2)", "0", "0"],
+            &["0", "r^2", "0"],
+            &["0", "0", "r^2*sin(theta)^2"],
+        ]), &sphere_coords).unwrap();
+        assert!(calculate_cotton_tensor(&sphere, &sphere_coords).unwrap().components.is_empty());
+        
+        // Nil geometry, dx^2 + dy^2 + (dz - x dy)^2, is not conformally flat
+        let nil = parse_metric_tensor(strings(&[
+            &["1", "0", "0"],
+            &["0", "1 + x^2", "-x"],
+            &["0", "-x", "1"],
+        ]), &coords).unwrap();
+        assert!(!calculate_cotton_tensor(&nil, &coords).unwrap().components.is_empty());
+        
+        let four = vec![vec![SymbolicExpr::One; 4]; 4];
+        assert!(calculate_cotton_tensor(&four, &["t".to_string(), "x".to_string(), "y".to_string(), "z".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_conformal_factor() {
+        let coords: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let minkowski = parse_metric_tensor(strings(&[
+            &["-1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "1"],
+        ]), &coords).unwrap();
+        let conformally_flat = parse_metric_tensor(strings(&[
+            &["-t^2", "0", "0", "0"],
+            &["0", "t^2", "0", "0"],
+            &["0", "0", "t^2", "0"],
+            &["0", "0", "0", "t^2"],
+        ]), &coords).unwrap();
+
+        let factor = conformal_factor_to(&conformally_flat, &minkowski).unwrap().unwrap();
+        crate::assert_expr_eq!(factor, SymbolicExpr::parse("t^2").unwrap());
+
+        let stretched = parse_metric_tensor(strings(&[
+            &["-t^2", "0", "0", "0"],
+            &["0", "t^2", "0", "0"],
+            &["0", "0", "t^2", "0"],
+            &["0", "0", "0", "x^2"],
+        ]), &coords).unwrap();
+        assert!(conformal_factor_to(&stretched, &minkowski).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_transform_metric() {
+        let cartesian: Vec<String> = ["t", "x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let spherical: Vec<String> = ["t", "r", "theta", "phi"].iter().map(|s| s.to_string()).collect();
+        let minkowski = parse_diagonal_metric(strings(&[&["-1", "1", "1", "1"]]).remove(0), &cartesian).unwrap();
+        let transform: Vec<SymbolicExpr> = ["t", "r*sin(theta)*cos(phi)", "r*sin(theta)*sin(phi)", "r*cos(theta)"]
+            .iter()
+            .map(|x| SymbolicExpr::parse(x).unwrap())
+            .collect();
+
+        // Compared as text, since a simplified `1` may be `One` or `Constant(1.0)`
+        let rendered = |metric: &MetricTensor| -> Vec<Vec<String>> {
+            metric.iter().map(|row| row.iter().map(|component| component.to_string()).collect()).collect()
+        };
+        let transformed = transform_metric(&minkowski, &cartesian, &spherical, &transform).unwrap();
+        let expected = parse_diagonal_metric(strings(&[&["-1", "1", "r^2", "r^2 * sin(theta)^2"]]).remove(0), &spherical).unwrap();
+        assert_eq!(rendered(&transformed), rendered(&expected));
+
+        // Coordinates are substituted simultaneously, so swapping them works
+        let plane = parse_diagonal_metric(strings(&[&["1", "x^2"]]).remove(0), &cartesian[1..3]).unwrap();
+        let swap = [SymbolicExpr::Variable("y".to_string()), SymbolicExpr::Variable("x".to_string())];
+        let swapped = transform_metric(&plane, &cartesian[1..3], &cartesian[1..3], &swap).unwrap();
+        assert_eq!(rendered(&swapped), [["y^2", "0"], ["0", "1"]]);
+
+        assert!(transform_metric(&minkowski, &cartesian, &spherical, &transform[..3]).is_err());
+    }
+}